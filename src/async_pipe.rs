@@ -0,0 +1,134 @@
+//! This module contains a runtime-agnostic async pipe built on `async-io`,
+//! usable from `smol`, `async-std` or any other executor.
+
+use crate::{create_fifo, write_without_sigpipe, Error, OnCleanup, PipeBuilder, PipeKind, Result};
+use async_io::Async;
+use fcntl::OFlag;
+use futures_io::{AsyncRead, AsyncWrite};
+use nix::errno::Errno;
+use nix::sys::stat::Mode;
+use nix::{fcntl, unistd};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Abstraction over a named pipe implementing `futures::io::AsyncRead` and
+/// `futures::io::AsyncWrite`. It is opened the same way as a `Pipe`, but
+/// reads and writes through a single non-blocking descriptor driven by the
+/// `async-io` reactor.
+#[derive(Debug)]
+pub struct AsyncPipe {
+    inner: Async<OwnedFd>,
+    path: PathBuf,
+    delete: OnCleanup,
+    sigpipe_safe: bool,
+}
+
+impl AsyncPipe {
+    /// Open or create a pipe. See `Pipe::open`.
+    pub fn open(path: &Path, on_cleanup: OnCleanup) -> Result<Self> {
+        PipeBuilder::new().on_cleanup(on_cleanup).open_async(path)
+    }
+
+    /// Open or create a pipe with the given name. Note that this is just a
+    /// string name, not a path.
+    pub fn with_name(name: &str) -> Result<Self> {
+        PipeBuilder::new().with_name_async(name)
+    }
+
+    /// Create a pipe with a randomly generated name in a tempory directory.
+    #[cfg(feature = "rand")]
+    pub fn create() -> Result<Self> {
+        PipeBuilder::new().open_async(&crate::random_path())
+    }
+
+    /// Open a pipe at the given path using the options of a `PipeBuilder`.
+    /// Only FIFOs are supported. The pipe never blocks the calling thread, so
+    /// `nonblocking` makes no difference, and with `write_only` opening fails
+    /// with `ENXIO` if there is no reader yet.
+    pub(crate) fn open_with(path: &Path, builder: &PipeBuilder) -> Result<Self> {
+        if builder.kind != PipeKind::Fifo {
+            Err(Error::Ipipe("AsyncPipe only supports FIFOs"))?;
+        }
+        builder.policy.check_owner(path)?;
+        create_fifo(path)?;
+        let access = match builder.write_only {
+            true => OFlag::O_WRONLY,
+            false => OFlag::O_RDWR,
+        };
+        let fd = fcntl::open(
+            path,
+            access | OFlag::O_NOCTTY | OFlag::O_NONBLOCK,
+            Mode::empty(),
+        )?;
+        Ok(AsyncPipe {
+            inner: Async::new(unsafe { OwnedFd::from_raw_fd(fd) })?,
+            path: path.to_path_buf(),
+            delete: builder.on_cleanup,
+            sigpipe_safe: builder.sigpipe_safe,
+        })
+    }
+
+    /// Return the path to this named pipe
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets the name of this pipe
+    pub fn name(&self) -> Option<&std::ffi::OsStr> {
+        self.path.file_name()
+    }
+}
+
+impl AsyncRead for AsyncPipe {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bytes: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match unistd::read(self.inner.get_ref().as_raw_fd(), bytes) {
+                Err(Errno::EAGAIN) => ready!(self.inner.poll_readable(cx))?,
+                result => break Poll::Ready(result.map_err(io::Error::from)),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncPipe {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bytes: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let fd = self.inner.get_ref().as_fd();
+            let written = match self.sigpipe_safe {
+                true => write_without_sigpipe(fd, bytes),
+                false => unistd::write(fd, bytes),
+            };
+            match written {
+                Err(Errno::EAGAIN) => ready!(self.inner.poll_writable(cx))?,
+                result => break Poll::Ready(result.map_err(io::Error::from)),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for AsyncPipe {
+    fn drop(&mut self) {
+        if let OnCleanup::Delete = self.delete {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
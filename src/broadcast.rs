@@ -0,0 +1,221 @@
+//! This module contains a broadcaster that copies everything written to one
+//! FIFO into any number of subscriber FIFOs.
+
+use crate::{
+    path_from_name, write_without_sigpipe, Interest, OnCleanup, Pipe, Result, Selector, PIPE_BUF,
+};
+use fcntl::OFlag;
+use nix::errno::Errno;
+use nix::fcntl;
+use nix::sys::stat::{stat, Mode, SFlag};
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::fd::{AsFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// What a `Broadcaster` does with a subscriber that does not keep up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlowSubscriber {
+    /// Drop the messages that do not fit in the subscriber's FIFO
+    #[default]
+    Drop,
+    /// Close and delete the subscriber's FIFO
+    Disconnect,
+}
+
+/// Copies every message written to an input FIFO into each subscriber FIFO,
+/// since a FIFO on its own hands each byte to only one reader. Subscribers
+/// join by creating a FIFO named `<input>.sub.<id>` next to the input, for
+/// example with `Broadcaster::subscribe`, and leave by deleting it. Writes
+/// to subscribers never block: subscribers that do not keep up are handled
+/// according to a `SlowSubscriber` policy.
+///
+/// ```no_run
+/// use ipipe::Broadcaster;
+/// use std::io::{BufRead, BufReader};
+///
+/// let mut broadcaster = Broadcaster::with_name("my_broadcast").unwrap();
+/// let path = broadcaster.path().to_path_buf();
+/// std::thread::spawn(move || broadcaster.run());
+///
+/// let subscriber = Broadcaster::subscribe(&path, "viewer").unwrap();
+/// for line in BufReader::new(subscriber).lines() {
+///     println!("{}", line.unwrap());
+/// }
+/// ```
+pub struct Broadcaster {
+    input: Pipe,
+    selector: Selector,
+    subscribers: HashMap<String, OwnedFd>,
+    slow: SlowSubscriber,
+    dropped: u64,
+    refresh_interval: Duration,
+    last_refresh: Option<Instant>,
+}
+
+impl Broadcaster {
+    /// Open or create the input FIFO at the given path. It is deleted when
+    /// the broadcaster is dropped.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut input = Pipe::open(path, OnCleanup::Delete)?;
+        let mut selector = Selector::new()?;
+        selector.register(&mut input, 0, Interest::READABLE)?;
+        Ok(Broadcaster {
+            input,
+            selector,
+            subscribers: HashMap::new(),
+            slow: SlowSubscriber::Drop,
+            dropped: 0,
+            refresh_interval: Duration::from_millis(100),
+            last_refresh: None,
+        })
+    }
+
+    /// Open or create the input FIFO with the given name. Note that this is
+    /// just a string name, not a path.
+    pub fn with_name(name: &str) -> Result<Self> {
+        Broadcaster::open(&path_from_name(name))
+    }
+
+    /// Set what happens to subscribers that do not keep up
+    pub fn slow_subscribers(mut self, slow: SlowSubscriber) -> Self {
+        self.slow = slow;
+        self
+    }
+
+    /// Set how often `run` looks for new subscribers. Defaults to 100ms.
+    pub fn refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    /// Return the path to the input FIFO
+    pub fn path(&self) -> &Path {
+        self.input.path()
+    }
+
+    /// Return the path of the subscriber FIFO with the given id for the
+    /// input FIFO at the given path
+    pub fn subscriber_path(path: &Path, id: &str) -> PathBuf {
+        let mut subscriber = path.as_os_str().to_owned();
+        subscriber.push(format!(".sub.{}", id));
+        subscriber.into()
+    }
+
+    /// Subscribe to the broadcaster whose input FIFO is at the given path by
+    /// creating the subscriber FIFO with the given id, which is deleted when
+    /// the returned pipe is dropped
+    pub fn subscribe(path: &Path, id: &str) -> Result<Pipe> {
+        Pipe::open(&Broadcaster::subscriber_path(path, id), OnCleanup::Delete)
+    }
+
+    /// The number of subscribers currently connected
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /// The number of bytes dropped because subscribers did not keep up
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Connect to new subscriber FIFOs and forget the ones that were deleted
+    pub fn refresh(&mut self) -> Result<()> {
+        self.last_refresh = Some(Instant::now());
+        let path = self.input.path().to_path_buf();
+        let prefix = match path.file_name() {
+            Some(name) => format!("{}.sub.", name.to_string_lossy()),
+            None => return Ok(()),
+        };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        self.subscribers
+            .retain(|id, _| Broadcaster::subscriber_path(&path, id).exists());
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let id = match name.to_str().and_then(|name| name.strip_prefix(&prefix)) {
+                Some(id) if !id.is_empty() && !self.subscribers.contains_key(id) => id,
+                _ => continue,
+            };
+            let subscriber = entry.path();
+            let is_fifo = stat(&subscriber).is_ok_and(|file_stat| {
+                file_stat.st_mode & SFlag::S_IFMT.bits() == SFlag::S_IFIFO.bits()
+            });
+            if !is_fifo {
+                continue;
+            }
+            // ENXIO means nobody has the subscriber FIFO open for reading yet
+            match fcntl::open(
+                &subscriber,
+                OFlag::O_WRONLY | OFlag::O_NONBLOCK | OFlag::O_NOCTTY,
+                Mode::empty(),
+            ) {
+                Ok(fd) => {
+                    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+                    self.subscribers.insert(id.to_string(), fd);
+                }
+                Err(Errno::ENXIO) | Err(Errno::ENOENT) => continue,
+                Err(e) => Err(e)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy a message to every subscriber, returning how many received it.
+    /// Messages of up to `PIPE_BUF` bytes are either copied whole or not at
+    /// all. Subscribers whose reader went away are disconnected.
+    pub fn broadcast(&mut self, message: &[u8]) -> Result<usize> {
+        let mut received = 0;
+        let mut disconnected = vec![];
+        for (id, fd) in &self.subscribers {
+            let written = match write_without_sigpipe(fd.as_fd(), message) {
+                Ok(written) => written,
+                Err(Errno::EAGAIN) => 0,
+                Err(_) => {
+                    disconnected.push(id.clone());
+                    continue;
+                }
+            };
+            if written == message.len() {
+                received += 1;
+            } else if self.slow == SlowSubscriber::Disconnect {
+                disconnected.push(id.clone());
+            } else {
+                self.dropped += (message.len() - written) as u64;
+            }
+        }
+        for id in disconnected {
+            self.subscribers.remove(&id);
+            let _ = std::fs::remove_file(Broadcaster::subscriber_path(self.input.path(), &id));
+        }
+        Ok(received)
+    }
+
+    /// Copy everything written to the input FIFO to the subscribers,
+    /// looking for new subscribers every refresh interval. Only returns if
+    /// reading the input fails.
+    pub fn run(&mut self) -> Result<()> {
+        let mut buffer = [0; PIPE_BUF];
+        loop {
+            let next_refresh = match self.last_refresh {
+                Some(last) => last + self.refresh_interval,
+                None => Instant::now(),
+            };
+            let now = Instant::now();
+            if now >= next_refresh {
+                self.refresh()?;
+                continue;
+            }
+            if self.selector.select(Some(next_refresh - now))?.is_empty() {
+                continue;
+            }
+            let read = self.input.read(&mut buffer)?;
+            self.broadcast(&buffer[..read])?;
+        }
+    }
+}
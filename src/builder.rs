@@ -0,0 +1,169 @@
+//! This module contains a builder for configuring how pipes are opened.
+
+#[cfg(all(unix, feature = "async-io"))]
+use crate::AsyncPipe;
+use crate::{path_from_name, OnCleanup, Pipe, Result};
+#[cfg(unix)]
+use crate::{PeerPolicy, PipeServer};
+use std::path::Path;
+
+/// The kind of object backing a pipe
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum PipeKind {
+    /// A FIFO on Unix, or a named pipe on Windows. This stays the default
+    /// with the `uds` feature enabled.
+    #[default]
+    Fifo,
+    /// A `SOCK_STREAM` Unix domain socket. The first pipe opened at a path
+    /// listens on it, and the next pipe opened at that path connects to it.
+    #[cfg(all(unix, feature = "uds"))]
+    Socket,
+}
+
+/// Options used to open a pipe. `Pipe::open`, `Pipe::with_name` and
+/// `Pipe::create` are equivalent to using a default builder.
+///
+/// ```no_run
+/// use ipipe::{OnCleanup, PipeBuilder};
+///
+/// let pipe = PipeBuilder::new()
+///     .on_cleanup(OnCleanup::Delete)
+///     .with_name("my_pipe")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct PipeBuilder {
+    pub(crate) kind: PipeKind,
+    pub(crate) on_cleanup: OnCleanup,
+    #[cfg(unix)]
+    pub(crate) policy: PeerPolicy,
+    #[cfg(unix)]
+    pub(crate) nonblocking: bool,
+    #[cfg(unix)]
+    pub(crate) write_only: bool,
+    #[cfg(unix)]
+    pub(crate) sigpipe_safe: bool,
+}
+
+impl Default for PipeBuilder {
+    fn default() -> Self {
+        PipeBuilder {
+            kind: PipeKind::Fifo,
+            on_cleanup: OnCleanup::NoDelete,
+            #[cfg(unix)]
+            policy: PeerPolicy::default(),
+            #[cfg(unix)]
+            nonblocking: false,
+            #[cfg(unix)]
+            write_only: false,
+            #[cfg(unix)]
+            sigpipe_safe: false,
+        }
+    }
+}
+
+impl PipeBuilder {
+    /// Create a builder with the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Choose the kind of object backing the pipe
+    pub fn kind(mut self, kind: PipeKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Choose whether the pipe is deleted when it is dropped
+    pub fn on_cleanup(mut self, on_cleanup: OnCleanup) -> Self {
+        self.on_cleanup = on_cleanup;
+        self
+    }
+
+    /// Open the pipe in non-blocking mode. See `Pipe::set_nonblocking`.
+    #[cfg(unix)]
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Open only the write end of the FIFO instead of both ends. Writes then
+    /// fail with `EPIPE` once every reader has gone away, rather than filling
+    /// the FIFO, and reads fail. Opening blocks until a reader arrives, or in
+    /// non-blocking mode fails with `ENXIO` if there is none. Has no effect
+    /// on sockets.
+    #[cfg(unix)]
+    pub fn write_only(mut self, write_only: bool) -> Self {
+        self.write_only = write_only;
+        self
+    }
+
+    /// Protect writes from `SIGPIPE`. See `Pipe::set_sigpipe_safe`.
+    #[cfg(unix)]
+    pub fn sigpipe_safe(mut self, sigpipe_safe: bool) -> Self {
+        self.sigpipe_safe = sigpipe_safe;
+        self
+    }
+
+    /// Only allow peers running as one of the given uids. Socket peers are
+    /// checked when they connect, while FIFOs are checked on a best-effort
+    /// basis against the owner of the FIFO. Rejected peers surface as
+    /// `Error::Unauthorized`.
+    #[cfg(unix)]
+    pub fn allow_uids<I: IntoIterator<Item = u32>>(mut self, uids: I) -> Self {
+        self.policy.uids = Some(uids.into_iter().collect());
+        self
+    }
+
+    /// Only allow peers running as one of the given gids. See `allow_uids`.
+    #[cfg(unix)]
+    pub fn allow_gids<I: IntoIterator<Item = u32>>(mut self, gids: I) -> Self {
+        self.policy.gids = Some(gids.into_iter().collect());
+        self
+    }
+
+    /// Open or create a pipe at the given path. Note that this function is
+    /// not platform-agnostic as unix pipe paths and Windows pipe paths are
+    /// formatted differently.
+    pub fn open(&self, path: &Path) -> Result<Pipe> {
+        Pipe::open_with(path, self)
+    }
+
+    /// Open or create a pipe with the given name. Note that this is just a
+    /// string name, not a path.
+    pub fn with_name(&self, name: &str) -> Result<Pipe> {
+        self.open(&path_from_name(name))
+    }
+
+    /// Create a pipe with a randomly generated name in a temporary directory.
+    #[cfg(feature = "rand")]
+    pub fn create(&self) -> Result<Pipe> {
+        self.open(&crate::random_path())
+    }
+
+    /// Open or create an `AsyncPipe` at the given path.
+    #[cfg(all(unix, feature = "async-io"))]
+    pub fn open_async(&self, path: &Path) -> Result<AsyncPipe> {
+        AsyncPipe::open_with(path, self)
+    }
+
+    /// Open or create an `AsyncPipe` with the given name. Note that this is
+    /// just a string name, not a path.
+    #[cfg(all(unix, feature = "async-io"))]
+    pub fn with_name_async(&self, name: &str) -> Result<AsyncPipe> {
+        self.open_async(&path_from_name(name))
+    }
+
+    /// Bind a `PipeServer` to the registration FIFO at the given path.
+    #[cfg(unix)]
+    pub fn server(&self, path: &Path) -> Result<PipeServer> {
+        PipeServer::bind_with(path, self)
+    }
+
+    /// Bind a `PipeServer` with the given name. Note that this is just a
+    /// string name, not a path.
+    #[cfg(unix)]
+    pub fn server_with_name(&self, name: &str) -> Result<PipeServer> {
+        self.server(&path_from_name(name))
+    }
+}
@@ -0,0 +1,308 @@
+//! This module contains a compression layer for byte streams, enabled by the
+//! `zstd` and `lz4` features.
+
+use crate::{Error, Framed, Result, DEFAULT_MAX_FRAME_LEN};
+use std::io::{BufRead, ErrorKind, Read, Write};
+
+#[cfg(feature = "zstd")]
+const ZSTD: u8 = 1;
+#[cfg(feature = "lz4")]
+const LZ4: u8 = 2;
+
+/// The number of bytes a `CompressedWriter` buffers before compressing them
+/// into a frame unless told otherwise
+pub const DEFAULT_COMPRESSED_FRAME_LEN: usize = 64 * 1024;
+
+/// The algorithm a `CompressedWriter` compresses frames with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Zstandard at the given level, where 0 picks the default level
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    /// LZ4 block compression
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Default for Compression {
+    /// Zstandard at its default level if the `zstd` feature is enabled,
+    /// otherwise LZ4
+    fn default() -> Self {
+        #[cfg(feature = "zstd")]
+        return Compression::Zstd(0);
+        #[cfg(not(feature = "zstd"))]
+        Compression::Lz4
+    }
+}
+
+impl Compression {
+    fn compress(&self, data: &[u8]) -> Result<(u8, Vec<u8>)> {
+        match *self {
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => Ok((ZSTD, zstd::bulk::compress(data, level)?)),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok((LZ4, lz4_flex::block::compress(data))),
+        }
+    }
+}
+
+/// Decompresses the body of a frame with the algorithm it was compressed with
+fn decompress(algorithm: u8, body: &[u8], len: usize) -> Result<Vec<u8>> {
+    let data = match algorithm {
+        #[cfg(feature = "zstd")]
+        ZSTD => zstd::bulk::decompress(body, len)?,
+        #[cfg(feature = "lz4")]
+        LZ4 => lz4_flex::block::decompress(body, len)
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?,
+        _ => Err(Error::Ipipe(
+            "Frame compressed with an unsupported algorithm",
+        ))?,
+    };
+    if data.len() != len {
+        Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "Decompressed frame has the wrong length",
+        ))?;
+    }
+    Ok(data)
+}
+
+/// Statistics about the frames passing through a `CompressedWriter` or a
+/// `CompressedReader`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompressionStats {
+    /// Frames written or read
+    pub frames: u64,
+    /// Bytes before compression
+    pub uncompressed: u64,
+    /// Bytes on the stream, including the frame headers
+    pub compressed: u64,
+}
+
+impl CompressionStats {
+    /// How many times smaller the data is on the stream, or 1 if nothing
+    /// went through yet
+    pub fn ratio(&self) -> f64 {
+        match self.compressed {
+            0 => 1.0,
+            compressed => self.uncompressed as f64 / compressed as f64,
+        }
+    }
+
+    fn record(&mut self, uncompressed: usize, frame_len: usize) {
+        self.frames += 1;
+        self.uncompressed += uncompressed as u64;
+        self.compressed += (frame_len + 4) as u64;
+    }
+}
+
+/// Compresses everything written to it into frames on the underlying stream,
+/// for a `CompressedReader` to read. Each frame is compressed on its own, so
+/// a reader may start at any frame boundary, for instance after restarting.
+/// Bytes are buffered until a frame is full, until `flush` is called, or
+/// until the writer is dropped, while `write_frame` writes a frame right
+/// away. Frames are written with `Framed`, and are only atomic if they fit
+/// in `PIPE_BUF` bytes, so there should be a single writer.
+///
+/// ```no_run
+/// use ipipe::{CompressedReader, CompressedWriter, Pipe};
+/// use std::io::{BufRead, Write};
+///
+/// let pipe = Pipe::with_name("telemetry").unwrap();
+/// let mut writer = CompressedWriter::new(pipe.clone());
+/// writeln!(writer, "{{\"cpu\": 0.42}}").unwrap();
+/// writer.flush().unwrap();
+///
+/// let mut reader = CompressedReader::new(pipe);
+/// let mut line = String::new();
+/// reader.read_line(&mut line).unwrap();
+/// println!("{} at {:.1}x", line, reader.stats().ratio());
+/// ```
+#[derive(Debug)]
+pub struct CompressedWriter<W: Write> {
+    framed: Framed<W>,
+    compression: Compression,
+    buffer: Vec<u8>,
+    frame_len: usize,
+    stats: CompressionStats,
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Compress frames of up to `DEFAULT_COMPRESSED_FRAME_LEN` bytes with
+    /// the default compression into the given stream
+    pub fn new(inner: W) -> Self {
+        CompressedWriter {
+            framed: Framed::new(inner),
+            compression: Compression::default(),
+            buffer: vec![],
+            frame_len: DEFAULT_COMPRESSED_FRAME_LEN,
+            stats: CompressionStats::default(),
+        }
+    }
+
+    /// Compress frames with the given algorithm instead
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Buffer up to the given number of bytes per frame instead
+    pub fn frame_len(mut self, frame_len: usize) -> Self {
+        self.frame_len = frame_len.max(1);
+        self
+    }
+
+    /// Get the statistics of the frames written so far
+    pub fn stats(&self) -> CompressionStats {
+        self.stats
+    }
+
+    /// Get a mutable reference to the underlying stream
+    pub fn get_mut(&mut self) -> &mut W {
+        self.framed.get_mut()
+    }
+
+    /// Write the buffered bytes, if any, then the given bytes as a frame of
+    /// their own
+    pub fn write_frame(&mut self, data: &[u8]) -> Result<()> {
+        self.write_buffer()?;
+        let (algorithm, compressed) = self.compression.compress(data)?;
+        let len = u32::try_from(data.len()).map_err(|_| Error::Ipipe("Frame too long"))?;
+        let mut frame = Vec::with_capacity(5 + compressed.len());
+        frame.push(algorithm);
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(&compressed);
+        self.framed.write_frame(&frame)?;
+        self.stats.record(data.len(), frame.len());
+        Ok(())
+    }
+
+    fn write_buffer(&mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            self.write_frame(&buffer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        let len = bytes.len().min(self.frame_len - self.buffer.len());
+        self.buffer.extend_from_slice(&bytes[..len]);
+        if self.buffer.len() >= self.frame_len {
+            self.write_buffer()?;
+        }
+        Ok(len)
+    }
+
+    /// Compresses the buffered bytes into a frame
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_buffer()?;
+        self.framed.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for CompressedWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.write_buffer();
+    }
+}
+
+/// Reads the frames written by a `CompressedWriter` and decompresses them.
+/// It reads whole frames, so it may start reading at any frame boundary, and
+/// implements `BufRead` so it can be handed to a `LineReader` or a
+/// `RecordReader`.
+#[derive(Debug)]
+pub struct CompressedReader<R> {
+    framed: Framed<R>,
+    frame: Vec<u8>,
+    pos: usize,
+    max_len: usize,
+    stats: CompressionStats,
+}
+
+impl<R: Read> CompressedReader<R> {
+    /// Read frames of up to `DEFAULT_MAX_FRAME_LEN` bytes, before and after
+    /// decompression, from the given stream
+    pub fn new(inner: R) -> Self {
+        CompressedReader {
+            framed: Framed::new(inner),
+            frame: vec![],
+            pos: 0,
+            max_len: DEFAULT_MAX_FRAME_LEN,
+            stats: CompressionStats::default(),
+        }
+    }
+
+    /// Read frames of up to the given length instead
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.framed = self.framed.max_len(max_len);
+        self.max_len = max_len;
+        self
+    }
+
+    /// Get the statistics of the frames read so far
+    pub fn stats(&self) -> CompressionStats {
+        self.stats
+    }
+
+    /// Get a mutable reference to the underlying stream
+    pub fn get_mut(&mut self) -> &mut R {
+        self.framed.get_mut()
+    }
+
+    /// Read and decompress the next frame, or `None` if the stream ended
+    /// between frames. The bytes of the current frame that were not read
+    /// yet are skipped.
+    pub fn read_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        self.frame.clear();
+        self.pos = 0;
+        let Some(frame) = self.framed.read_frame()? else {
+            return Ok(None);
+        };
+        if frame.len() < 5 {
+            Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Compressed frame too short",
+            ))?;
+        }
+        let len = u32::from_be_bytes(frame[1..5].try_into().unwrap_or_default()) as usize;
+        if len > self.max_len {
+            Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Frame of {} bytes is longer than {}", len, self.max_len),
+            ))?;
+        }
+        let data = decompress(frame[0], &frame[5..], len)?;
+        self.stats.record(data.len(), frame.len());
+        Ok(Some(data))
+    }
+}
+
+impl<R: Read> Read for CompressedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = buf.len().min(available.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: Read> BufRead for CompressedReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        // Empty frames are skipped rather than read as the end of the stream
+        while self.pos >= self.frame.len() {
+            match self.read_frame()? {
+                Some(frame) => self.frame = frame,
+                None => break,
+            }
+        }
+        Ok(&self.frame[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.frame.len());
+    }
+}
@@ -0,0 +1,124 @@
+//! This module contains peer credentials and the policy used to check them.
+
+use crate::{Error, Result};
+use nix::sys::stat::stat;
+use std::path::Path;
+
+/// The identity of the process on the other end of a pipe
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Credentials {
+    pub uid: u32,
+    pub gid: u32,
+    /// Only available where the kernel reports it, as for Unix domain
+    /// sockets on Linux and Android
+    pub pid: Option<i32>,
+}
+
+impl Credentials {
+    /// Best-effort credentials taken from the owner of a file
+    pub(crate) fn from_owner(path: &Path) -> Result<Self> {
+        let file_stat = stat(path)?;
+        Ok(Credentials {
+            uid: file_stat.st_uid,
+            gid: file_stat.st_gid,
+            pid: None,
+        })
+    }
+
+    /// Credentials of the peer connected to a Unix domain socket
+    #[cfg(all(feature = "uds", any(target_os = "linux", target_os = "android")))]
+    pub(crate) fn from_socket<F: std::os::fd::AsFd>(fd: &F) -> Result<Self> {
+        use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+        let creds = getsockopt(fd, PeerCredentials)?;
+        Ok(Credentials {
+            uid: creds.uid(),
+            gid: creds.gid(),
+            pid: Some(creds.pid()),
+        })
+    }
+
+    /// Credentials of the peer connected to a Unix domain socket
+    #[cfg(all(
+        feature = "uds",
+        any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        )
+    ))]
+    pub(crate) fn from_socket<F: std::os::fd::AsFd>(fd: &F) -> Result<Self> {
+        let (uid, gid) = nix::unistd::getpeereid(fd)?;
+        Ok(Credentials {
+            uid: uid.as_raw(),
+            gid: gid.as_raw(),
+            pid: None,
+        })
+    }
+
+    /// Other platforms cannot tell who is on the other end of a Unix domain
+    /// socket, so the peer is rejected with unknown ids
+    #[cfg(all(
+        feature = "uds",
+        not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))
+    ))]
+    pub(crate) fn from_socket<F: std::os::fd::AsFd>(_fd: &F) -> Result<Self> {
+        Err(Error::Unauthorized(Credentials {
+            uid: u32::MAX,
+            gid: u32::MAX,
+            pid: None,
+        }))
+    }
+}
+
+/// The uids and gids a peer is allowed to have. A peer must satisfy every
+/// list that has been set.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PeerPolicy {
+    pub(crate) uids: Option<Vec<u32>>,
+    pub(crate) gids: Option<Vec<u32>>,
+}
+
+impl PeerPolicy {
+    /// Errors with `Error::Unauthorized` if the peer is not allowed
+    pub(crate) fn check(&self, creds: Credentials) -> Result<Credentials> {
+        let allowed = |list: &Option<Vec<u32>>, id| list.as_ref().is_none_or(|l| l.contains(&id));
+        if allowed(&self.uids, creds.uid) && allowed(&self.gids, creds.gid) {
+            Ok(creds)
+        } else {
+            Err(Error::Unauthorized(creds))
+        }
+    }
+
+    /// Checks the peer of a Unix domain socket, if any uids or gids are
+    /// required
+    #[cfg(feature = "uds")]
+    pub(crate) fn check_socket<F: std::os::fd::AsFd>(&self, fd: &F) -> Result<()> {
+        if self.uids.is_some() || self.gids.is_some() {
+            self.check(Credentials::from_socket(fd)?)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the owner of a FIFO. This is only a best effort, as anyone
+    /// with access may use a FIFO.
+    pub(crate) fn check_owner(&self, path: &Path) -> Result<()> {
+        if self.uids.is_some() || self.gids.is_some() {
+            if let Ok(creds) = Credentials::from_owner(path) {
+                self.check(creds)?;
+            }
+        }
+        Ok(())
+    }
+}
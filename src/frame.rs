@@ -0,0 +1,108 @@
+//! This module contains a length-prefixed framing layer for byte streams.
+
+use crate::{Error, Result};
+use std::io::{ErrorKind, IoSlice, Read, Write};
+
+/// The longest frame a `Framed` reads unless told otherwise
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Splits a byte stream into frames, each prefixed with its length as a
+/// big-endian `u32`. The prefix and the frame are handed to the stream in a
+/// single vectored write, which `Pipe` turns into a single `writev`, so
+/// frames of up to `PIPE_BUF` bytes including the prefix do not interleave
+/// with other writers. Streams that do not implement `write_vectored` write
+/// the prefix and the frame separately. Frames longer than the maximum
+/// length are rejected when read rather than allocated.
+///
+/// ```no_run
+/// use ipipe::{Framed, Pipe};
+///
+/// let pipe = Pipe::with_name("my_pipe").unwrap();
+/// let mut framed = Framed::new(pipe);
+/// framed.write_frame(b"Hello").unwrap();
+/// assert_eq!(Some(b"Hello".to_vec()), framed.read_frame().unwrap());
+/// ```
+#[derive(Debug)]
+pub struct Framed<T> {
+    inner: T,
+    max_len: usize,
+}
+
+impl<T> Framed<T> {
+    /// Frame the given stream, reading frames of up to
+    /// `DEFAULT_MAX_FRAME_LEN` bytes
+    pub fn new(inner: T) -> Self {
+        Framed {
+            inner,
+            max_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+
+    /// Read frames of up to the given length instead
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying stream
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Get the underlying stream
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Write> Framed<T> {
+    /// Write a frame
+    pub fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let len = u32::try_from(frame.len()).map_err(|_| Error::Ipipe("Frame too long"))?;
+        let header = len.to_be_bytes();
+        let mut bufs = [IoSlice::new(&header), IoSlice::new(frame)];
+        let mut bufs = &mut bufs[..];
+        while !bufs.is_empty() {
+            match self.inner.write_vectored(bufs) {
+                Ok(0) => Err(std::io::Error::from(ErrorKind::WriteZero))?,
+                Ok(written) => IoSlice::advance_slices(&mut bufs, written),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => Err(e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Read> Framed<T> {
+    /// Read the next frame, or `None` if the stream ended between frames. A
+    /// stream ending in the middle of a frame is an `UnexpectedEof` error.
+    pub fn read_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut header = [0; 4];
+        let mut read = 0;
+        while read < header.len() {
+            match self.inner.read(&mut header[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => Err(std::io::Error::from(ErrorKind::UnexpectedEof))?,
+                Ok(count) => read += count,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => Err(e)?,
+            }
+        }
+        let len = u32::from_be_bytes(header) as usize;
+        if len > self.max_len {
+            Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Frame of {} bytes is longer than {}", len, self.max_len),
+            ))?;
+        }
+        let mut frame = vec![0; len];
+        self.inner.read_exact(&mut frame)?;
+        Ok(Some(frame))
+    }
+}
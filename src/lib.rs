@@ -37,6 +37,9 @@
 //! platform agnostic, however, as Windows pipe paths require a special
 //! format.
 //!
+//! `PipeBuilder` offers the same three ways of opening a pipe along with
+//! extra options, such as choosing what kind of object backs the pipe.
+//!
 //! Calling `clone()` on a pipe will create a pipe who's handle exists as a Weak
 //! reference to the original pipe. That means dropping the original pipe will
 //! also close all of its clones. If a clone is in the middle of a read or write
@@ -47,6 +50,10 @@
 mod pipe_unix;
 #[cfg(unix)]
 pub use pipe_unix::*;
+#[cfg(unix)]
+mod server_unix;
+#[cfg(unix)]
+pub use server_unix::*;
 
 #[cfg(windows)]
 mod pipe_windows;
@@ -59,8 +66,84 @@ mod static_pipe;
 #[cfg(feature = "static_pipe")]
 pub use static_pipe::*;
 
+#[macro_use]
+mod pwrite;
+pub use pwrite::*;
+
+#[cfg(feature = "log")]
+mod logger;
+#[cfg(feature = "log")]
+pub use logger::*;
+
+#[cfg(feature = "tracing")]
+mod trace_layer;
+#[cfg(feature = "tracing")]
+pub use trace_layer::*;
+
+mod builder;
+pub use builder::*;
+
+mod line_reader;
+pub use line_reader::*;
+
+mod record;
+pub use record::*;
+
+mod frame;
+pub use frame::*;
+
+mod peek;
+pub use peek::*;
+
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+mod compress;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+pub use compress::*;
+
+#[cfg(unix)]
+pub mod rpc;
+
+#[cfg(unix)]
+mod broadcast;
+#[cfg(unix)]
+pub use broadcast::*;
+
+#[cfg(unix)]
+mod work_queue;
+#[cfg(unix)]
+pub use work_queue::*;
+
+#[cfg(unix)]
+mod reconnect;
+#[cfg(unix)]
+pub use reconnect::*;
+
+#[cfg(unix)]
+mod credentials;
+#[cfg(unix)]
+pub use credentials::Credentials;
+#[cfg(unix)]
+pub(crate) use credentials::PeerPolicy;
+
+#[cfg(all(unix, feature = "uds"))]
+mod uds;
+
+#[cfg(unix)]
+mod selector;
+#[cfg(unix)]
+pub use selector::*;
+
+#[cfg(all(unix, feature = "mio"))]
+mod mio_source;
+
+#[cfg(all(unix, feature = "async-io"))]
+mod async_pipe;
+#[cfg(all(unix, feature = "async-io"))]
+pub use async_pipe::*;
+
 mod handle;
 #[cfg(test)]
+#[allow(clippy::needless_borrow, clippy::char_lit_as_u8)]
 mod tests;
 pub(crate) use handle::*;
 
@@ -70,7 +153,7 @@ use std::sync::mpsc;
 #[cfg(all(feature = "tokio_channels", not(feature = "channels")))]
 use tokio::sync::mpsc;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum OnCleanup {
     Delete,
     NoDelete,
@@ -87,6 +170,31 @@ impl Pipe {
         self.path().file_name()
     }
 
+    /// Whether `write_vectored` hands all its buffers to the OS in a single
+    /// call, making it atomic for buffers adding up to at most `PIPE_BUF`
+    /// bytes, rather than writing only the first non-empty one
+    pub fn is_write_vectored(&self) -> bool {
+        cfg!(unix)
+    }
+
+    /// Read lines of at most `max_len` bytes from this pipe. See
+    /// `LineReader`.
+    pub fn lines_bounded(self, max_len: usize) -> LineReader<std::io::BufReader<Pipe>> {
+        LineReader::new(std::io::BufReader::new(self), max_len)
+    }
+
+    /// Read records ending with the given delimiter from this pipe. See
+    /// `RecordReader`.
+    pub fn records(self, delimiter: &[u8]) -> Result<RecordReader<std::io::BufReader<Pipe>>> {
+        RecordReader::new(std::io::BufReader::new(self), delimiter)
+    }
+
+    /// Allow peeking at this pipe before reading from it. See
+    /// `PeekablePipe`.
+    pub fn peekable(self) -> PeekablePipe<Pipe> {
+        PeekablePipe::new(self)
+    }
+
     /// Creates a receiver which all output from this pipe is directed into. A
     /// thread is spawned to read from the pipe, which will shutdown when the
     /// receiver is dropped. Note that the thread blocks, and may attempt to read
@@ -158,8 +266,10 @@ impl Pipe {
     }
 }
 
-/// Standard error type used by this library
+/// Standard error type used by this library. New variants may be added in
+/// minor versions, so matches need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     Ipipe(&'static str),
     InvalidPath,
@@ -167,6 +277,14 @@ pub enum Error {
     Io(std::io::Error),
     Native(&'static str, u32, String),
     Misc(String),
+    /// A line was longer than the limit of a `LineReader`
+    LineTooLong(usize),
+    /// The handler of an RPC call returned an error
+    Remote(String),
+    /// The peer on the other end of a pipe was rejected by the allowed
+    /// uids/gids
+    #[cfg(unix)]
+    Unauthorized(Credentials),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -180,6 +298,12 @@ impl std::fmt::Display for Error {
             Error::Io(err) => err.fmt(f),
             Error::Native(text, code, oss) => write!(f, "{}: {} - {}", text, code, oss),
             Error::Misc(s) => s.fmt(f),
+            Error::LineTooLong(max_len) => write!(f, "Line longer than {} bytes", max_len),
+            Error::Remote(s) => write!(f, "Remote error: {}", s),
+            #[cfg(unix)]
+            Error::Unauthorized(creds) => {
+                write!(f, "Unauthorized peer: uid {}, gid {}", creds.uid, creds.gid)
+            }
         }
     }
 }
@@ -189,7 +313,12 @@ impl From<Error> for std::io::Error {
     fn from(err: Error) -> std::io::Error {
         match err {
             Error::Io(err) => err,
-            e => std::io::Error::new(std::io::ErrorKind::Other, e),
+            e @ Error::LineTooLong(_) => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            #[cfg(unix)]
+            e @ Error::Unauthorized(_) => {
+                std::io::Error::new(std::io::ErrorKind::PermissionDenied, e)
+            }
+            e => std::io::Error::other(e),
         }
     }
 }
@@ -200,8 +329,8 @@ impl From<std::io::Error> for Error {
     }
 }
 
-impl<'a> From<std::sync::PoisonError<std::sync::MutexGuard<'a, Pipe>>> for Error {
-    fn from(err: std::sync::PoisonError<std::sync::MutexGuard<Pipe>>) -> Error {
+impl<T> From<std::sync::PoisonError<T>> for Error {
+    fn from(err: std::sync::PoisonError<T>) -> Error {
         Error::Misc(err.to_string())
     }
 }
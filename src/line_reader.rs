@@ -0,0 +1,144 @@
+//! This module contains a line reader which bounds the length of the lines it
+//! reads.
+
+use crate::{Error, Result};
+use std::io::{BufRead, ErrorKind};
+
+/// What ends a line read by a `LineReader`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Delimiter {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\n`, with a `\r` right before it also removed from the line
+    CrLf,
+    /// `\0`
+    Nul,
+}
+
+impl Delimiter {
+    fn byte(self) -> u8 {
+        match self {
+            Delimiter::Lf | Delimiter::CrLf => b'\n',
+            Delimiter::Nul => b'\0',
+        }
+    }
+}
+
+/// Reads lines of at most `max_len` bytes, not counting the delimiter, so that
+/// a writer that never sends a newline cannot make the reader allocate without
+/// bound. An overlong line is reported as `Error::LineTooLong` as soon as it
+/// crosses the limit, and the rest of it is skipped so that reading resumes at
+/// the next line.
+///
+/// As an iterator it yields lines as strings. Use `read_bytes` for lines that
+/// are not UTF-8.
+///
+/// ```no_run
+/// use ipipe::Pipe;
+///
+/// let pipe = Pipe::with_name("my_pipe").unwrap();
+/// for line in pipe.lines_bounded(1024) {
+///     match line {
+///         Ok(line) => println!("{}", line),
+///         Err(ipipe::Error::LineTooLong(_)) => eprintln!("Skipped a long line"),
+///         Err(e) => panic!("{}", e),
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LineReader<R> {
+    reader: R,
+    max_len: usize,
+    delimiter: Delimiter,
+    // Whether the rest of an overlong line still has to be skipped
+    skipping: bool,
+}
+
+impl<R: BufRead> LineReader<R> {
+    /// Read lines of at most `max_len` bytes ending with `\n`
+    pub fn new(reader: R, max_len: usize) -> Self {
+        LineReader {
+            reader,
+            max_len,
+            delimiter: Delimiter::Lf,
+            skipping: false,
+        }
+    }
+
+    /// End lines with the given delimiter instead
+    pub fn delimiter(mut self, delimiter: Delimiter) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Get the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read the next line without the delimiter, or `None` at the end of the
+    /// stream
+    pub fn read_bytes(&mut self) -> Option<Result<Vec<u8>>> {
+        let delimiter = self.delimiter.byte();
+        // A trailing \r is only known not to be part of a CRLF once more
+        // bytes arrive
+        let limit = match self.delimiter {
+            Delimiter::CrLf => self.max_len.saturating_add(1),
+            _ => self.max_len,
+        };
+        let mut line = vec![];
+        loop {
+            let (done, used) = match self.reader.fill_buf() {
+                Ok([]) => {
+                    self.skipping = false;
+                    return match line.is_empty() {
+                        true => None,
+                        false => Some(self.finish(line)),
+                    };
+                }
+                Ok(available) => {
+                    let (done, end) = match available.iter().position(|&b| b == delimiter) {
+                        Some(end) => (true, end),
+                        None => (false, available.len()),
+                    };
+                    if !self.skipping {
+                        let end = end.min(limit.saturating_add(1) - line.len());
+                        line.extend_from_slice(&available[..end]);
+                    }
+                    (done, if done { end + 1 } else { end })
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.reader.consume(used);
+            if self.skipping {
+                self.skipping = !done;
+            } else if done {
+                return Some(self.finish(line));
+            } else if line.len() > limit {
+                self.skipping = true;
+                return Some(Err(Error::LineTooLong(self.max_len)));
+            }
+        }
+    }
+
+    fn finish(&self, mut line: Vec<u8>) -> Result<Vec<u8>> {
+        if self.delimiter == Delimiter::CrLf && line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        match line.len() > self.max_len {
+            true => Err(Error::LineTooLong(self.max_len)),
+            false => Ok(line),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LineReader<R> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_bytes()
+            .map(|line| line.and_then(|line| Ok(String::from_utf8(line)?)))
+    }
+}
@@ -0,0 +1,111 @@
+//! This module contains a `log` backend that writes records into a static
+//! pipe.
+
+use crate::static_pipe;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+type Format = Box<dyn Fn(&Record) -> String + Send + Sync>;
+
+/// A logger that writes formatted records into a static pipe. Logging never
+/// blocks the caller for longer than the timeout: a record that cannot be
+/// written whole in time, for example because nobody is reading from the
+/// pipe, is dropped whole and counted, so readers never see part of one.
+///
+/// ```no_run
+/// use ipipe::PipeLogger;
+///
+/// let _reader = ipipe::init("my_log").unwrap();
+/// let logger = PipeLogger::new("my_log")
+///     .level(log::LevelFilter::Debug)
+///     .install()
+///     .unwrap();
+/// log::info!("Hello");
+/// println!("Dropped {} records", logger.dropped());
+/// ```
+pub struct PipeLogger {
+    name: String,
+    level: LevelFilter,
+    format: Format,
+    timeout: Duration,
+    dropped: AtomicU64,
+}
+
+impl PipeLogger {
+    /// Create a logger writing to the static pipe with the given name, which
+    /// must be initialized before records are logged. Defaults to the `Info`
+    /// level, a 10ms timeout and `LEVEL target: message` lines.
+    pub fn new(name: &str) -> Self {
+        PipeLogger {
+            name: name.to_string(),
+            level: LevelFilter::Info,
+            format: Box::new(|record| {
+                format!(
+                    "{} {}: {}\n",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                )
+            }),
+            timeout: Duration::from_millis(10),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Only log records at or above the given level
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Format records with the given function. The result is written as-is,
+    /// so it should normally end with a newline.
+    pub fn format<F: Fn(&Record) -> String + Send + Sync + 'static>(mut self, format: F) -> Self {
+        self.format = Box::new(format);
+        self
+    }
+
+    /// The longest the caller waits for a record to be written before it is
+    /// dropped
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The number of records that were dropped
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Install this logger as the global logger, returning a reference that
+    /// can be used to read the dropped record count.
+    pub fn install(self) -> Result<&'static PipeLogger, log::SetLoggerError> {
+        let level = self.level;
+        let logger: &'static PipeLogger = Box::leak(Box::new(self));
+        log::set_logger(logger)?;
+        log::set_max_level(level);
+        Ok(logger)
+    }
+}
+
+impl Log for PipeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = (self.format)(record);
+            if !static_pipe::registry().write_record(
+                self.name.as_str(),
+                line.as_bytes(),
+                self.timeout,
+            ) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
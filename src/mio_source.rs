@@ -0,0 +1,151 @@
+//! This module contains the `mio` integration, which lets non-blocking pipes
+//! be registered with a `mio::Poll`.
+
+use crate::selector::combine_fds;
+use crate::{Interest, Pipe, PipeConnection, Selectable};
+use mio::event::Source;
+use mio::unix::SourceFd;
+use mio::{Registry, Token};
+use std::io;
+
+/// Registers every descriptor the source reads or writes through. A pipe
+/// may hold separate read and write descriptors, both of which report under
+/// the same token. `registered` is the interest the source is currently
+/// registered with, if any: its descriptors are reregistered, or
+/// deregistered if no longer needed. Returns the interest registered, which
+/// `deregister` needs to find the same descriptors again.
+fn register<S: Selectable>(
+    source: &mut S,
+    nonblocking: bool,
+    registry: &Registry,
+    token: Token,
+    interests: mio::Interest,
+    registered: Interest,
+) -> io::Result<Interest> {
+    if !nonblocking {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Pipes must be in non-blocking mode to be registered with mio",
+        ))?;
+    }
+    let mut interest = Interest::empty();
+    if interests.is_readable() {
+        interest |= Interest::READABLE;
+    }
+    if interests.is_writable() {
+        interest |= Interest::WRITABLE;
+    }
+    let registered_fds = match registered.is_empty() {
+        true => vec![],
+        false => combine_fds(source.selector_fds(registered)?),
+    };
+    let fds = combine_fds(source.selector_fds(interest)?);
+    for (fd, _) in &registered_fds {
+        if !fds.iter().any(|(new, _)| new == fd) {
+            SourceFd(fd).deregister(registry)?;
+        }
+    }
+    for (fd, fd_interest) in fds {
+        let fd_interests = match (fd_interest.is_readable(), fd_interest.is_writable()) {
+            (true, true) => mio::Interest::READABLE | mio::Interest::WRITABLE,
+            (false, true) => mio::Interest::WRITABLE,
+            _ => mio::Interest::READABLE,
+        };
+        if registered_fds.iter().any(|(old, _)| *old == fd) {
+            SourceFd(&fd).reregister(registry, token, fd_interests)?;
+        } else {
+            SourceFd(&fd).register(registry, token, fd_interests)?;
+        }
+    }
+    Ok(interest)
+}
+
+/// Deregisters the descriptors registered for the given interest only, as
+/// asking for others could open a handle that was never registered
+fn deregister<S: Selectable>(
+    source: &mut S,
+    registry: &Registry,
+    interest: Interest,
+) -> io::Result<()> {
+    if interest.is_empty() {
+        return Ok(());
+    }
+    for (fd, _) in combine_fds(source.selector_fds(interest)?) {
+        SourceFd(&fd).deregister(registry)?;
+    }
+    Ok(())
+}
+
+impl Source for Pipe {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        let nonblocking = self.is_nonblocking();
+        self.mio_interest = register(
+            self,
+            nonblocking,
+            registry,
+            token,
+            interests,
+            Interest::empty(),
+        )?;
+        Ok(())
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        let nonblocking = self.is_nonblocking();
+        let registered = self.mio_interest;
+        self.mio_interest = register(self, nonblocking, registry, token, interests, registered)?;
+        Ok(())
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        let interest = std::mem::take(&mut self.mio_interest);
+        deregister(self, registry, interest)
+    }
+}
+
+impl Source for PipeConnection {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        let nonblocking = self.is_nonblocking();
+        self.mio_interest = register(
+            self,
+            nonblocking,
+            registry,
+            token,
+            interests,
+            Interest::empty(),
+        )?;
+        Ok(())
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        let nonblocking = self.is_nonblocking();
+        let registered = self.mio_interest;
+        self.mio_interest = register(self, nonblocking, registry, token, interests, registered)?;
+        Ok(())
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        let interest = std::mem::take(&mut self.mio_interest);
+        deregister(self, registry, interest)
+    }
+}
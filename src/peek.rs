@@ -0,0 +1,122 @@
+//! This module contains a reader which can look ahead without consuming.
+
+use std::io::{BufRead, ErrorKind, Read};
+
+/// How many bytes `fill_buf` asks the underlying reader for
+const CHUNK_LEN: usize = 8 * 1024;
+
+/// Wraps a reader, usually a `Pipe`, so that bytes can be peeked at before
+/// they are read. Peeked bytes are kept in an internal buffer and returned
+/// first by the following reads. It implements `BufRead`, so it can be handed
+/// to a `LineReader`, a `RecordReader` or a `Framed` once the right decoder
+/// has been chosen.
+///
+/// ```no_run
+/// use ipipe::{LineReader, Pipe};
+///
+/// let mut pipe = Pipe::with_name("my_pipe").unwrap().peekable();
+/// let mut magic = [0; 4];
+/// pipe.peek(&mut magic).unwrap();
+/// if &magic == b"TEXT" {
+///     for line in LineReader::new(pipe, 1024) {
+///         println!("{}", line.unwrap());
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PeekablePipe<R> {
+    inner: R,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl<R> PeekablePipe<R> {
+    /// Allow peeking at the given reader
+    pub fn new(inner: R) -> Self {
+        PeekablePipe {
+            inner,
+            buffer: vec![],
+            pos: 0,
+        }
+    }
+
+    /// The bytes peeked at or buffered but not read yet
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer[self.pos..]
+    }
+
+    /// Get a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader. Reading from it
+    /// directly skips the bytes in the buffer.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Get the underlying reader. The bytes in the buffer are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> PeekablePipe<R> {
+    /// Copy the next bytes into `buf` without consuming them, blocking until
+    /// there are enough bytes to fill it or the stream ends. Returns the
+    /// number of bytes copied, which is only less than `buf.len()` at the
+    /// end of the stream.
+    pub fn peek(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos > 0 {
+            self.buffer.drain(..self.pos);
+            self.pos = 0;
+        }
+        while self.buffer.len() < buf.len() {
+            let filled = self.buffer.len();
+            self.buffer.resize(buf.len(), 0);
+            let read = self.inner.read(&mut self.buffer[filled..]);
+            self.buffer.truncate(filled + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => break,
+                Ok(_) => continue,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        let len = buf.len().min(self.buffer.len());
+        buf[..len].copy_from_slice(&self.buffer[..len]);
+        Ok(len)
+    }
+}
+
+impl<R: Read> Read for PeekablePipe<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Large reads skip the buffer once it is empty
+        if self.pos >= self.buffer.len() && buf.len() >= CHUNK_LEN {
+            return self.inner.read(buf);
+        }
+        let available = self.fill_buf()?;
+        let len = buf.len().min(available.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: Read> BufRead for PeekablePipe<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pos >= self.buffer.len() {
+            self.buffer.resize(CHUNK_LEN, 0);
+            self.pos = 0;
+            let read = self.inner.read(&mut self.buffer);
+            self.buffer.truncate(*read.as_ref().unwrap_or(&0));
+            read?;
+        }
+        Ok(&self.buffer[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buffer.len());
+    }
+}
@@ -1,17 +1,39 @@
-use super::{Error, Handle, OnCleanup, Result};
+use super::{
+    Credentials, Error, Handle, Interest, OnCleanup, PipeBuilder, PipeKind, Result, Selectable,
+};
 use fcntl::OFlag;
 use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::signal::{SigSet, SigmaskHow, Signal};
+#[cfg(all(feature = "uds", any(target_os = "linux", target_os = "android")))]
+use nix::sys::socket::MsgFlags;
 use nix::sys::stat::{stat, Mode, SFlag};
-use nix::sys::termios::{tcflush, FlushArg};
+use nix::sys::uio;
 use nix::{fcntl, unistd};
-use std::fs::File;
-use std::os::fd::{AsFd, FromRawFd};
+use std::io::{IoSlice, IoSliceMut};
+#[cfg(all(feature = "uds", not(any(target_os = "linux", target_os = "android"))))]
+use std::os::fd::AsFd;
+#[cfg(feature = "uds")]
+use std::os::fd::AsRawFd;
+use std::os::fd::{BorrowedFd, RawFd};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Weak};
 
 #[cfg(feature = "rand")]
 use rand::distributions::Alphanumeric;
 
+#[cfg(feature = "uds")]
+use crate::uds::Socket;
+
+/// A snapshot of how full a pipe is, as returned by `Pipe::stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PipeStats {
+    /// Bytes waiting to be read
+    pub bytes_available: usize,
+    /// Bytes the kernel can hold for the pipe, where the platform reports it
+    pub capacity: Option<usize>,
+}
+
 /// Abstraction over a named pipe
 #[derive(Default)]
 pub struct Pipe {
@@ -20,6 +42,13 @@ pub struct Pipe {
     pub(super) path: PathBuf,
     pub(super) is_slave: bool,
     delete: Option<OnCleanup>,
+    nonblocking: bool,
+    write_only: bool,
+    sigpipe_safe: bool,
+    #[cfg(feature = "mio")]
+    pub(crate) mio_interest: Interest,
+    #[cfg(feature = "uds")]
+    socket: Option<Arc<Socket>>,
 }
 
 impl Pipe {
@@ -28,71 +57,290 @@ impl Pipe {
     /// Note that this function is not platform-agnostic as unix pipe paths and
     /// Windows pipe paths are formatted differnetly.
     pub fn open(path: &Path, on_cleanup: OnCleanup) -> Result<Self> {
-        let mode = Mode::S_IWUSR | Mode::S_IRUSR | Mode::S_IRGRP | Mode::S_IWGRP;
-
-        if let Some(_) = path.parent() {
-            match stat(path) {
-                Ok(file_stat) => {
-                    // Error out if file is not a named pipe
-                    if file_stat.st_mode & SFlag::S_IFIFO.bits() == 0 {
-                        Err(Error::InvalidPath)?;
-                    }
-                }
-                Err(Errno::ENOENT) => {
-                    unistd::mkfifo(path, mode)?;
-                }
-                err => {
-                    err?;
-                }
-            }
+        create_fifo(path)?;
+        Pipe::init_handle(path, false, false).map(|handle| Pipe {
+            handle1: Some(handle),
+            handle2: None,
+            path: path.to_path_buf(),
+            is_slave: false,
+            delete: Some(on_cleanup),
+            nonblocking: false,
+            write_only: false,
+            sigpipe_safe: false,
+            #[cfg(feature = "mio")]
+            mio_interest: Interest::empty(),
+            #[cfg(feature = "uds")]
+            socket: None,
+        })
+    }
 
-            Pipe::init_handle(path).map(|handle| Pipe {
+    /// Open or create a pipe which only has the write end of the FIFO open,
+    /// so that writes fail with `EPIPE` once every reader has gone away. In
+    /// non-blocking mode opening fails with `ENXIO` while there is no reader,
+    /// otherwise it blocks until one arrives.
+    fn open_write_only(path: &Path, on_cleanup: OnCleanup, nonblocking: bool) -> Result<Self> {
+        create_fifo(path)?;
+        Pipe::init_handle(path, nonblocking, true).map(|mut handle| {
+            handle.set_type(HandleType::Write);
+            Pipe {
                 handle1: Some(handle),
                 handle2: None,
                 path: path.to_path_buf(),
                 is_slave: false,
                 delete: Some(on_cleanup),
-            })
-        } else {
-            Err(Error::InvalidPath)
-        }
+                nonblocking,
+                write_only: true,
+                sigpipe_safe: false,
+                #[cfg(feature = "mio")]
+                mio_interest: Interest::empty(),
+                #[cfg(feature = "uds")]
+                socket: None,
+            }
+        })
     }
 
     /// Open or create a pipe with the given name. Note that this is just a
     /// string name, not a path.
+    ///
+    /// This always opens a FIFO, even with the `uds` feature enabled, so that
+    /// enabling the feature does not change what existing code opens. Use
+    /// `PipeBuilder::kind` with `PipeKind::Socket` to open a socket instead.
     pub fn with_name(name: &str) -> Result<Self> {
-        let path = PathBuf::from(format!("/tmp/{}", name));
-        Pipe::open(&path, OnCleanup::NoDelete)
+        Pipe::open(&path_from_name(name), OnCleanup::NoDelete)
     }
 
     /// Create a pipe with a randomly generated name in a tempory directory.
     #[cfg(feature = "rand")]
     pub fn create() -> Result<Self> {
-        // Generate a random path name
+        Pipe::open(&random_path(), OnCleanup::NoDelete)
+    }
 
-        use rand::distributions::DistString;
-        let path = PathBuf::from(format!(
-            "/tmp/pipe_{}_{}",
-            std::process::id(),
-            Alphanumeric.sample_string(&mut rand::thread_rng(), 15)
-        ));
+    /// Open a pipe at the given path using the options of a `PipeBuilder`.
+    pub(crate) fn open_with(path: &Path, builder: &PipeBuilder) -> Result<Self> {
+        let mut pipe = match builder.kind {
+            PipeKind::Fifo => {
+                builder.policy.check_owner(path)?;
+                match builder.write_only {
+                    true => Pipe::open_write_only(path, builder.on_cleanup, builder.nonblocking),
+                    false => Pipe::open(path, builder.on_cleanup),
+                }
+            }
+            #[cfg(feature = "uds")]
+            PipeKind::Socket => Socket::open(path, builder.policy.clone()).map(|socket| Pipe {
+                handle1: None,
+                handle2: None,
+                path: path.to_path_buf(),
+                is_slave: false,
+                delete: Some(builder.on_cleanup),
+                nonblocking: false,
+                write_only: false,
+                sigpipe_safe: false,
+                #[cfg(feature = "mio")]
+                mio_interest: Interest::empty(),
+                socket: Some(Arc::new(socket)),
+            }),
+        }?;
+        if builder.nonblocking {
+            pipe.set_nonblocking(true)?;
+        }
+        pipe.sigpipe_safe = builder.sigpipe_safe;
+        Ok(pipe)
+    }
 
-        Pipe::open(&path, OnCleanup::NoDelete)
+    /// Moves the pipe into or out of non-blocking mode. In non-blocking mode
+    /// reads and writes that cannot make progress fail with
+    /// `std::io::ErrorKind::WouldBlock`. This also applies to clones that
+    /// share the pipe's handles.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            socket.set_nonblocking(nonblocking)?;
+        }
+        for raw in [&self.handle1, &self.handle2]
+            .into_iter()
+            .flatten()
+            .filter_map(Handle::raw)
+        {
+            let flags = OFlag::from_bits_truncate(fcntl::fcntl(raw, fcntl::F_GETFL)?);
+            let flags = if nonblocking {
+                flags | OFlag::O_NONBLOCK
+            } else {
+                flags & !OFlag::O_NONBLOCK
+            };
+            fcntl::fcntl(raw, fcntl::F_SETFL(flags))?;
+        }
+        self.nonblocking = nonblocking;
+        Ok(())
+    }
+
+    /// Whether the pipe is in non-blocking mode
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking
+    }
+
+    /// Choose whether writes are protected from `SIGPIPE`. Writing to a pipe
+    /// nobody reads from raises `SIGPIPE`, which kills the process unless it
+    /// handles or ignores the signal. When protected, `SIGPIPE` is blocked on
+    /// the writing thread for the duration of each write, or not raised at
+    /// all for sockets where the platform allows it, and the write fails
+    /// with `std::io::ErrorKind::BrokenPipe` instead. This also applies to
+    /// clones made afterwards.
+    pub fn set_sigpipe_safe(&mut self, sigpipe_safe: bool) {
+        self.sigpipe_safe = sigpipe_safe;
+    }
+
+    /// Whether writes are protected from `SIGPIPE`. See `set_sigpipe_safe`.
+    pub fn is_sigpipe_safe(&self) -> bool {
+        self.sigpipe_safe
+    }
+
+    /// Whether the pipe only has the write end of the FIFO open. See
+    /// `PipeBuilder::write_only`.
+    pub fn is_write_only(&self) -> bool {
+        self.write_only
+    }
+
+    /// Write to the pipe without blocking for longer than the timeout. Bytes
+    /// are written in chunks of at most `PIPE_BUF` once the pipe has room
+    /// for them, so a buffer that fits in `PIPE_BUF` is written atomically or
+    /// not at all. Fails with `std::io::ErrorKind::TimedOut` if nothing could
+    /// be written in time, otherwise returns the number of bytes written.
+    pub fn write_timeout(
+        &mut self,
+        bytes: &[u8],
+        timeout: std::time::Duration,
+    ) -> std::io::Result<usize> {
+        let deadline = std::time::Instant::now() + timeout;
+        let fd = self.selector_fds(Interest::WRITABLE)?[0].0;
+        let mut written = 0;
+        while written < bytes.len() {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let mut poll_fd = [PollFd::new(
+                unsafe { BorrowedFd::borrow_raw(fd) },
+                PollFlags::POLLOUT,
+            )];
+            let ready = match poll(
+                &mut poll_fd,
+                PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX),
+            ) {
+                Err(Errno::EINTR) => continue,
+                ready => ready?,
+            };
+            if ready == 0 {
+                break;
+            }
+            let chunk = &bytes[written..bytes.len().min(written + PIPE_BUF)];
+            written += std::io::Write::write(self, chunk)?;
+        }
+        match written {
+            0 if !bytes.is_empty() => Err(std::io::ErrorKind::TimedOut.into()),
+            written => Ok(written),
+        }
+    }
+
+    /// The number of bytes waiting to be read, as reported by `FIONREAD`
+    pub fn bytes_available(&self) -> Result<usize> {
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            return Ok(unread_bytes(socket.stream()?.as_raw_fd())?);
+        }
+        Ok(unread_bytes(self.raw_fd()?)?)
+    }
+
+    /// The number of bytes the kernel can hold for the FIFO before writes
+    /// block, as reported by `F_GETPIPE_SZ`. Only supported for FIFOs on
+    /// Linux and Android.
+    pub fn capacity(&self) -> Result<usize> {
+        #[cfg(feature = "uds")]
+        if self.socket.is_some() {
+            Err(Error::Ipipe("Sockets do not report a capacity"))?;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return Ok(fcntl::fcntl(self.raw_fd()?, fcntl::F_GETPIPE_SZ)? as usize);
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        Err(Error::Ipipe(
+            "Pipe capacity is not reported on this platform",
+        ))
+    }
+
+    /// Take a snapshot of how full the pipe is
+    pub fn stats(&self) -> Result<PipeStats> {
+        Ok(PipeStats {
+            bytes_available: self.bytes_available()?,
+            capacity: self.capacity().ok(),
+        })
+    }
+
+    /// Any open descriptor of the FIFO
+    fn raw_fd(&self) -> Result<RawFd> {
+        [&self.handle1, &self.handle2]
+            .into_iter()
+            .flatten()
+            .find_map(Handle::raw)
+            .ok_or(Errno::EBADF.into())
+    }
+
+    /// Wait until everything written to the FIFO has been read. The wait is
+    /// bounded by the timeout, whether or not anyone is reading: the number
+    /// of unread bytes is checked with `FIONREAD` every millisecond, and the
+    /// call fails with `std::io::ErrorKind::TimedOut` as soon as the timeout
+    /// elapses with bytes still unread. A zero timeout checks only once. No
+    /// descriptor is opened, so the call never blocks on opening the FIFO.
+    /// Sockets are not supported.
+    pub fn drain(&mut self, timeout: std::time::Duration) -> std::io::Result<()> {
+        #[cfg(feature = "uds")]
+        if self.socket.is_some() {
+            return Err(Error::Ipipe("Sockets cannot be drained").into());
+        }
+        let deadline = std::time::Instant::now() + timeout;
+        let fd = self.raw_fd()?;
+        while unread_bytes(fd)? > 0 {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+            std::thread::sleep(remaining.min(std::time::Duration::from_millis(1)));
+        }
+        Ok(())
+    }
+
+    /// Leave the FIFO in place when the pipe is dropped, whatever its cleanup
+    /// policy. Used when another pipe has taken over the path.
+    #[cfg(feature = "static_pipe")]
+    pub(crate) fn keep_path(&mut self) {
+        self.delete = Some(OnCleanup::NoDelete);
+    }
+
+    /// Returns the credentials of the peer. For sockets these are reported
+    /// by the kernel, blocking until a peer connects if needed. For FIFOs
+    /// they are a best-effort guess based on the owner of the FIFO.
+    pub fn peer_credentials(&self) -> Result<Credentials> {
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            return Credentials::from_socket(&*socket.stream()?);
+        }
+        Credentials::from_owner(&self.path)
     }
 
     /// Close a named pipe
     pub fn close(self) -> Result<()> {
-        if let Some(raw) = self.handle1.as_ref().unwrap().raw() {
-            unistd::close(raw).map_err(Error::from)
-        } else {
-            Ok(())
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            socket.close();
+            return Ok(());
+        }
+        for handle in [self.handle1.as_ref(), self.handle2.as_ref()] {
+            if let Some(raw) = handle.and_then(Handle::raw) {
+                unistd::close(raw)?;
+            }
         }
+        Ok(())
     }
 
-    fn init_handle(path: &Path) -> Result<Handle> {
+    fn init_handle(path: &Path, nonblocking: bool, write_only: bool) -> Result<Handle> {
         let mode = Mode::S_IWUSR | Mode::S_IRUSR | Mode::S_IRGRP | Mode::S_IWGRP;
 
-        if let Some(_) = path.parent() {
+        if path.parent().is_some() {
             match stat(path) {
                 Ok(file_stat) => {
                     // Error out if file is not a named pipe
@@ -105,7 +353,14 @@ impl Pipe {
                 }
             }
 
-            fcntl::open(path, OFlag::O_RDWR | OFlag::O_NOCTTY, mode)
+            let mut flags = match write_only {
+                true => OFlag::O_WRONLY | OFlag::O_NOCTTY,
+                false => OFlag::O_RDWR | OFlag::O_NOCTTY,
+            };
+            if nonblocking {
+                flags |= OFlag::O_NONBLOCK;
+            }
+            fcntl::open(path, flags, mode)
                 .map(|handle| Handle::Arc(Arc::new(handle), HandleType::Unknown))
                 .map_err(Error::from)
         } else {
@@ -114,14 +369,17 @@ impl Pipe {
     }
 
     fn init_handle_type(&mut self, handle_type: HandleType) -> Result<std::os::unix::io::RawFd> {
+        if self.write_only && handle_type == HandleType::Read {
+            Err(nix::errno::Errno::EBADF)?;
+        }
         if self.handle1.as_ref().unwrap().handle_type() == HandleType::Unknown {
             self.handle1.as_mut().unwrap().set_type(handle_type);
         }
         if self.handle1.as_ref().unwrap().handle_type() == handle_type {
             self.handle1.as_ref().unwrap().raw()
         } else {
-            if let None = self.handle2 {
-                let mut handle = Pipe::init_handle(&self.path)?;
+            if self.handle2.is_none() {
+                let mut handle = Pipe::init_handle(&self.path, self.nonblocking, false)?;
                 handle.set_type(handle_type);
                 self.handle2 = Some(handle);
             }
@@ -134,29 +392,103 @@ impl Pipe {
 
 impl std::io::Write for Pipe {
     fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
-        let handle = self.init_handle_type(HandleType::Write)?;
-        unistd::write(unsafe { File::from_raw_fd(handle).as_fd() }, bytes)
-            .map_err(Error::from)
-            .map_err(std::io::Error::from)
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            let stream = socket.stream()?;
+            if !self.sigpipe_safe {
+                return std::io::Write::write(&mut &*stream, bytes);
+            }
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            return nix::sys::socket::send(stream.as_raw_fd(), bytes, MsgFlags::MSG_NOSIGNAL)
+                .map_err(std::io::Error::from);
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            return write_without_sigpipe(stream.as_fd(), bytes).map_err(std::io::Error::from);
+        }
+        let handle = unsafe { BorrowedFd::borrow_raw(self.init_handle_type(HandleType::Write)?) };
+        match self.sigpipe_safe {
+            true => write_without_sigpipe(handle, bytes),
+            false => unistd::write(handle, bytes),
+        }
+        .map_err(std::io::Error::from)
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        let handle = self.init_handle_type(HandleType::Write)?;
-        tcflush(
-            unsafe { File::from_raw_fd(handle).as_fd() },
-            FlushArg::TCOFLUSH,
-        )
-        .map_err(Error::from)
+    /// Writes all the buffers with a single `writev`, so that buffers adding
+    /// up to at most `PIPE_BUF` bytes are written atomically.
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            let stream = socket.stream()?;
+            if !self.sigpipe_safe {
+                return std::io::Write::write_vectored(&mut &*stream, bufs);
+            }
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            return nix::sys::socket::sendmsg::<()>(
+                stream.as_raw_fd(),
+                bufs,
+                &[],
+                MsgFlags::MSG_NOSIGNAL,
+                None,
+            )
+            .map_err(std::io::Error::from);
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            return without_sigpipe(|| uio::writev(&*stream, bufs)).map_err(std::io::Error::from);
+        }
+        let handle = unsafe { BorrowedFd::borrow_raw(self.init_handle_type(HandleType::Write)?) };
+        match self.sigpipe_safe {
+            true => without_sigpipe(|| uio::writev(handle, bufs)),
+            false => uio::writev(handle, bufs),
+        }
         .map_err(std::io::Error::from)
     }
+
+    /// Writes are not buffered, so this does nothing. See `Pipe::drain` to
+    /// wait until the written bytes have been read.
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 
 impl std::io::Read for Pipe {
     fn read(&mut self, bytes: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            return std::io::Read::read(&mut &*socket.stream()?, bytes);
+        }
         let handle = self.init_handle_type(HandleType::Read)?;
-        unistd::read(handle, bytes)
-            .map_err(Error::from)
-            .map_err(std::io::Error::from)
+        unistd::read(handle, bytes).map_err(std::io::Error::from)
+    }
+
+    /// Reads into all the buffers with a single `readv`
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            return std::io::Read::read_vectored(&mut &*socket.stream()?, bufs);
+        }
+        let handle = unsafe { BorrowedFd::borrow_raw(self.init_handle_type(HandleType::Read)?) };
+        uio::readv(handle, bufs).map_err(std::io::Error::from)
+    }
+}
+
+impl Selectable for Pipe {
+    /// Reads and writes may go through two different descriptors, which are
+    /// both registered under the same token. Sockets block until connected.
+    fn selector_fds(&mut self, interest: Interest) -> Result<Vec<(RawFd, Interest)>> {
+        #[cfg(feature = "uds")]
+        if let Some(socket) = self.socket.as_ref() {
+            return Ok(vec![(socket.stream()?.as_raw_fd(), interest)]);
+        }
+        let mut fds = vec![];
+        let read_interest = interest & (Interest::READABLE | Interest::HANGUP);
+        if !read_interest.is_empty() {
+            fds.push((self.init_handle_type(HandleType::Read)?, read_interest));
+        }
+        if interest.is_writable() {
+            fds.push((
+                self.init_handle_type(HandleType::Write)?,
+                Interest::WRITABLE,
+            ));
+        }
+        Ok(fds)
     }
 }
 
@@ -165,11 +497,16 @@ impl Drop for Pipe {
         if !self.is_slave {
             self.handle1 = Some(Handle::Weak(Weak::new(), HandleType::Unknown));
             self.handle2 = None;
-            if let Some(del) = self.delete.as_ref() {
-                if let OnCleanup::Delete = del {
-                    std::fs::remove_file(&self.path).unwrap();
+            #[cfg(feature = "uds")]
+            if let Some(socket) = self.socket.take() {
+                socket.close();
+                if !socket.is_listener() {
+                    return;
                 }
             }
+            if let Some(OnCleanup::Delete) = self.delete.as_ref() {
+                let _ = std::fs::remove_file(&self.path);
+            }
         }
     }
 }
@@ -184,10 +521,109 @@ impl Clone for Pipe {
             path: self.path.clone(),
             is_slave: true,
             delete: Some(OnCleanup::NoDelete),
+            nonblocking: self.nonblocking,
+            write_only: self.write_only,
+            sigpipe_safe: self.sigpipe_safe,
+            #[cfg(feature = "mio")]
+            mio_interest: Interest::empty(),
+            #[cfg(feature = "uds")]
+            socket: self.socket.clone(),
         }
     }
 }
 
+/// Writes to a descriptor with `SIGPIPE` blocked on the calling thread, so
+/// that a missing reader surfaces as `EPIPE` rather than as a signal. See
+/// `without_sigpipe`.
+pub(crate) fn write_without_sigpipe(fd: BorrowedFd, bytes: &[u8]) -> nix::Result<usize> {
+    without_sigpipe(|| unistd::write(fd, bytes))
+}
+
+/// Runs a write with `SIGPIPE` blocked on the calling thread. A `SIGPIPE`
+/// raised by the write is discarded before the signal mask is restored,
+/// while one that was already pending is left alone.
+fn without_sigpipe(write: impl FnOnce() -> nix::Result<usize>) -> nix::Result<usize> {
+    let mut sigpipe = SigSet::empty();
+    sigpipe.add(Signal::SIGPIPE);
+    let was_pending = sigpipe_pending();
+    let mask = sigpipe.thread_swap_mask(SigmaskHow::SIG_BLOCK)?;
+    let written = write();
+    if written == Err(Errno::EPIPE) && !was_pending && sigpipe_pending() {
+        // The signal is pending, so this returns right away
+        sigpipe.wait()?;
+    }
+    mask.thread_set_mask()?;
+    written
+}
+
+/// Whether a `SIGPIPE` is pending for the calling thread or the process
+fn sigpipe_pending() -> bool {
+    let mut pending = std::mem::MaybeUninit::<libc::sigset_t>::uninit();
+    unsafe {
+        libc::sigpending(pending.as_mut_ptr()) == 0
+            && libc::sigismember(pending.as_ptr(), libc::SIGPIPE) == 1
+    }
+}
+
+nix::ioctl_read_bad!(fionread, libc::FIONREAD, libc::c_int);
+
+/// The number of bytes waiting to be read from the FIFO behind a descriptor,
+/// which may be either end of it
+fn unread_bytes(fd: RawFd) -> nix::Result<usize> {
+    let mut unread = 0;
+    unsafe { fionread(fd, &mut unread) }?;
+    Ok(unread as usize)
+}
+
+/// Creates a FIFO at the given path if it does not exist, erroring out if
+/// something other than a FIFO is already there.
+pub(crate) fn create_fifo(path: &Path) -> Result<()> {
+    let mode = Mode::S_IWUSR | Mode::S_IRUSR | Mode::S_IRGRP | Mode::S_IWGRP;
+
+    if path.parent().is_some() {
+        match stat(path) {
+            Ok(file_stat) => {
+                // Error out if file is not a named pipe
+                if file_stat.st_mode & SFlag::S_IFIFO.bits() == 0 {
+                    Err(Error::InvalidPath)?;
+                }
+            }
+            Err(Errno::ENOENT) => {
+                unistd::mkfifo(path, mode)?;
+            }
+            err => {
+                err?;
+            }
+        }
+        Ok(())
+    } else {
+        Err(Error::InvalidPath)
+    }
+}
+
+/// The largest write the system guarantees to be atomic on a pipe
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const PIPE_BUF: usize = 4096;
+/// The largest write the system guarantees to be atomic on a pipe
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub const PIPE_BUF: usize = 512;
+
+/// Resolves a pipe name to the path used by `Pipe::with_name`
+pub(crate) fn path_from_name(name: &str) -> PathBuf {
+    PathBuf::from(format!("/tmp/{}", name))
+}
+
+/// Generates a random path in a temporary directory
+#[cfg(feature = "rand")]
+pub(crate) fn random_path() -> PathBuf {
+    use rand::distributions::DistString;
+    PathBuf::from(format!(
+        "/tmp/pipe_{}_{}",
+        std::process::id(),
+        Alphanumeric.sample_string(&mut rand::thread_rng(), 15)
+    ))
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum HandleType {
     Read,
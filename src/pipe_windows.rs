@@ -1,8 +1,8 @@
-use super::{Handle, OnCleanup, Result};
+use super::{Handle, OnCleanup, PipeBuilder, Result};
 use std::ffi::OsString;
 use std::io::{self, Read, Write};
 use std::os::windows::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use winapi::{
     shared::minwindef::{DWORD, LPCVOID, LPVOID},
@@ -38,24 +38,25 @@ impl Pipe {
     /// Open a pipe with the given name. Note that this is just a string name,
     /// not a path.
     pub fn with_name(name: &str) -> Result<Self> {
-        let path_string = format!(r"\\.\pipe\{}", name);
-        Pipe::open(&Path::new(&path_string), OnCleanup::Delete)
+        Pipe::open(&path_from_name(name), OnCleanup::Delete)
     }
 
     /// Open a pipe with a randomly generated name.
     #[cfg(feature = "rand")]
     pub fn create() -> Result<Self> {
-        // Generate a random path name
-        use rand::distributions::DistString;
-        let path_string = format!(
-            r"\\.\pipe\pipe_{}_{}",
-            std::process::id(),
-            Alphanumeric.sample_string(&mut rand::thread_rng(), 15)
-        );
+        Pipe::open(&random_path(), OnCleanup::Delete)
+    }
 
-        Pipe::open(&Path::new(&path_string), OnCleanup::Delete)
+    /// Open a pipe at the given path using the options of a `PipeBuilder`.
+    pub(crate) fn open_with(path: &Path, builder: &PipeBuilder) -> Result<Self> {
+        Pipe::open(path, builder.on_cleanup)
     }
 
+    /// Named pipes on Windows go away with their last handle rather than
+    /// being deleted, so there is no path to keep.
+    #[cfg(feature = "static_pipe")]
+    pub(crate) fn keep_path(&mut self) {}
+
     /// Close a named pipe
     pub fn close(self) -> Result<()> {
         if let Some(mut handle) = self.handle {
@@ -75,6 +76,19 @@ impl Pipe {
         Ok(())
     }
 
+    /// Write to the pipe. The timeout is not enforced on Windows and only
+    /// exists for parity with unix. Writes through a client handle, which is
+    /// put in `PIPE_NOWAIT` mode, return at once when the pipe buffer is
+    /// full, but writes through the server end of the pipe are synchronous
+    /// and block until a client reads.
+    pub fn write_timeout(
+        &mut self,
+        bytes: &[u8],
+        _: std::time::Duration,
+    ) -> std::io::Result<usize> {
+        self.write(bytes)
+    }
+
     /// Creates a new pipe handle
     fn create_pipe(path: &Path) -> io::Result<Handle> {
         let mut os_str: OsString = path.as_os_str().into();
@@ -326,6 +340,25 @@ impl Write for Handle {
     }
 }
 
+/// The largest write this crate treats as atomic
+pub const PIPE_BUF: usize = 4096;
+
+/// Resolves a pipe name to the path used by `Pipe::with_name`
+pub(crate) fn path_from_name(name: &str) -> PathBuf {
+    PathBuf::from(format!(r"\\.\pipe\{}", name))
+}
+
+/// Generates a random pipe path
+#[cfg(feature = "rand")]
+pub(crate) fn random_path() -> PathBuf {
+    use rand::distributions::DistString;
+    PathBuf::from(format!(
+        r"\\.\pipe\pipe_{}_{}",
+        std::process::id(),
+        Alphanumeric.sample_string(&mut rand::thread_rng(), 15)
+    ))
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum HandleType {
     Server,
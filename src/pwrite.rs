@@ -0,0 +1,140 @@
+//! This module contains the `pwrite!` and `pwriteln!` macros, which format
+//! straight into a pipe or a static pipe.
+
+use crate::{Result, PIPE_BUF};
+use std::fmt;
+#[cfg(feature = "static_pipe")]
+use std::{borrow::Borrow, hash::Hash};
+
+/// Write formatted text to a `PipeTarget`, returning the number of bytes
+/// written. The target is the name of a static pipe, a registry paired with
+/// a key, or a writer such as a `Pipe`, and the text is formatted without
+/// allocating. Text that fits
+/// in `PIPE_BUF` bytes is written with a single write, which the OS
+/// guarantees not to interleave with writes from other threads or processes.
+///
+/// ```no_run
+/// # use ipipe::{pwrite, Pipe};
+/// let mut pipe = Pipe::with_name("my_pipe").unwrap();
+/// let written = pwrite!(pipe, "{} + {} = {}", 1, 1, 2).unwrap();
+/// ```
+#[macro_export]
+macro_rules! pwrite
+{
+    ($target:expr, $($arg:tt)*) => ({
+        use $crate::PipeTarget as _;
+        $target.pwrite_fmt(format_args!($($arg)*))
+    });
+}
+
+/// Write formatted text and a trailing newline to a `PipeTarget`. See
+/// `pwrite!`.
+#[macro_export]
+macro_rules! pwriteln
+{
+    ($target:expr) => ($crate::pwrite!($target, "\n"));
+    ($target:expr, $($arg:tt)*) => ({
+        use $crate::PipeTarget as _;
+        $target.pwrite_fmt(format_args!("{}\n", format_args!($($arg)*)))
+    });
+}
+
+/// Something `pwrite!` and `pwriteln!` can write to: a `Pipe`, a mutable
+/// reference to any writer, the name of a static pipe of the global
+/// registry, or a `(&PipeRegistry, &key)` pair for a static pipe of any
+/// registry.
+pub trait PipeTarget {
+    /// Write all of the bytes, returning how many were accepted
+    fn pwrite_bytes(&mut self, bytes: &[u8]) -> Result<usize>;
+
+    /// Format into a buffer of `PIPE_BUF` bytes on the stack and write it
+    /// once it is full or the formatting is done
+    fn pwrite_fmt(&mut self, args: fmt::Arguments) -> Result<usize> {
+        let mut buffer = Buffer {
+            target: self,
+            bytes: [0; PIPE_BUF],
+            len: 0,
+            written: 0,
+            error: None,
+        };
+        let result = fmt::Write::write_fmt(&mut buffer, args).and_then(|_| buffer.flush());
+        match (result, buffer.error) {
+            (_, Some(err)) => Err(err),
+            (Err(err), None) => Err(crate::Error::Misc(err.to_string())),
+            (Ok(()), None) => Ok(buffer.written),
+        }
+    }
+}
+
+impl PipeTarget for crate::Pipe {
+    fn pwrite_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        std::io::Write::write_all(self, bytes)?;
+        Ok(bytes.len())
+    }
+}
+
+impl<W: std::io::Write + ?Sized> PipeTarget for &mut W {
+    fn pwrite_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        self.write_all(bytes)?;
+        Ok(bytes.len())
+    }
+}
+
+#[cfg(feature = "static_pipe")]
+impl PipeTarget for &str {
+    fn pwrite_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        crate::static_pipe::registry().write(*self, bytes)
+    }
+}
+
+#[cfg(feature = "static_pipe")]
+impl<K, Q> PipeTarget for (&crate::PipeRegistry<K>, &Q)
+where
+    K: 'static + AsRef<str> + Hash + Ord + Clone + Send + Sync + Borrow<Q>,
+    Q: ?Sized + Hash + Ord,
+{
+    fn pwrite_bytes(&mut self, bytes: &[u8]) -> Result<usize> {
+        self.0.write(self.1, bytes)
+    }
+}
+
+/// Collects formatted text into chunks of up to `PIPE_BUF` bytes
+struct Buffer<'a, T: ?Sized> {
+    target: &'a mut T,
+    bytes: [u8; PIPE_BUF],
+    len: usize,
+    written: usize,
+    error: Option<crate::Error>,
+}
+
+impl<T: PipeTarget + ?Sized> Buffer<'_, T> {
+    fn flush(&mut self) -> fmt::Result {
+        if self.len > 0 {
+            match self.target.pwrite_bytes(&self.bytes[..self.len]) {
+                Ok(written) => self.written += written,
+                Err(err) => {
+                    self.error = Some(err);
+                    return Err(fmt::Error);
+                }
+            }
+            self.len = 0;
+        }
+        Ok(())
+    }
+}
+
+impl<T: PipeTarget + ?Sized> fmt::Write for Buffer<'_, T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            if self.len == PIPE_BUF {
+                self.flush()?;
+            }
+            let count = bytes.len().min(PIPE_BUF - self.len);
+            self.bytes[self.len..self.len + count].copy_from_slice(&bytes[..count]);
+            self.len += count;
+            bytes = &bytes[count..];
+        }
+        Ok(())
+    }
+}
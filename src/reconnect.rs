@@ -0,0 +1,250 @@
+//! This module contains a writer which outlives the readers of its FIFO.
+
+use crate::{create_fifo, path_from_name, Error, Pipe, PipeBuilder, Result};
+use nix::errno::Errno;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a `ReconnectingWriter` waits between attempts to reopen its FIFO
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Wait the same time after every failed attempt
+    Fixed(Duration),
+    /// Wait `initial` after the first failed attempt and twice as long after
+    /// each following one, up to `max`
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::Exponential {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Backoff {
+    /// The time to wait after the given number of consecutive failed attempts
+    pub fn delay(&self, failures: u32) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => initial
+                .checked_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+                .map_or(max, |delay| delay.min(max)),
+        }
+    }
+}
+
+/// Writes to a FIFO whose reader may come and go. The FIFO is opened with
+/// only its write end, see `PipeBuilder::write_only`, so a reader going away
+/// is noticed as soon as a write fails with `EPIPE`, without raising
+/// `SIGPIPE`. While no reader is connected, each write is kept in memory as
+/// one message, up to a limit of bytes and of messages beyond which the
+/// oldest messages are dropped. The writer tries to reopen the FIFO on the
+/// following writes, spaced out according to a `Backoff`, and replays the
+/// kept messages in order once a reader is back. Writes therefore only fail
+/// if the FIFO cannot be opened for another reason than a missing reader.
+///
+/// A message that was only partly written when the reader went away is
+/// replayed whole.
+///
+/// ```no_run
+/// use ipipe::ReconnectingWriter;
+/// use std::io::Write;
+///
+/// let mut writer = ReconnectingWriter::with_name("my_pipe")
+///     .unwrap()
+///     .max_bytes(1024 * 1024);
+/// loop {
+///     writeln!(writer, "Still running").unwrap();
+///     std::thread::sleep(std::time::Duration::from_secs(1));
+/// }
+/// ```
+pub struct ReconnectingWriter {
+    path: PathBuf,
+    pipe: Option<Pipe>,
+    backlog: VecDeque<Vec<u8>>,
+    backlog_len: usize,
+    max_bytes: usize,
+    max_messages: usize,
+    backoff: Backoff,
+    failures: u32,
+    next_attempt: Option<Instant>,
+    dropped: u64,
+}
+
+impl ReconnectingWriter {
+    /// Open or create the FIFO at the given path. Succeeds whether or not a
+    /// reader is connected.
+    pub fn open(path: &Path) -> Result<Self> {
+        create_fifo(path)?;
+        let mut writer = ReconnectingWriter {
+            path: path.to_path_buf(),
+            pipe: None,
+            backlog: VecDeque::new(),
+            backlog_len: 0,
+            max_bytes: 64 * 1024,
+            max_messages: usize::MAX,
+            backoff: Backoff::default(),
+            failures: 0,
+            next_attempt: None,
+            dropped: 0,
+        };
+        writer.reconnect()?;
+        Ok(writer)
+    }
+
+    /// Open or create the FIFO with the given name. Note that this is just a
+    /// string name, not a path.
+    pub fn with_name(name: &str) -> Result<Self> {
+        ReconnectingWriter::open(&path_from_name(name))
+    }
+
+    /// Set the most bytes kept while disconnected. Defaults to 64 KiB.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Set the most messages kept while disconnected. Unlimited by default.
+    pub fn max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    /// Set how long to wait between attempts to reopen the FIFO. The next
+    /// write tries right away.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self.next_attempt = None;
+        self
+    }
+
+    /// Return the path to the FIFO
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether a reader was connected as of the last write
+    pub fn is_connected(&self) -> bool {
+        self.pipe.is_some()
+    }
+
+    /// The number of bytes waiting for a reader
+    pub fn buffered(&self) -> usize {
+        self.backlog_len
+    }
+
+    /// The number of messages dropped because too many were waiting
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Try to reopen the FIFO right away, regardless of the backoff, and
+    /// replay the waiting messages. Returns whether a reader is connected.
+    pub fn reconnect(&mut self) -> Result<bool> {
+        if self.pipe.is_none() {
+            let opened = PipeBuilder::new()
+                .write_only(true)
+                .nonblocking(true)
+                .sigpipe_safe(true)
+                .open(&self.path);
+            match opened {
+                Ok(mut pipe) => {
+                    if let Err(e) = pipe.set_nonblocking(false) {
+                        let _ = pipe.close();
+                        return Err(e);
+                    }
+                    self.pipe = Some(pipe);
+                    self.failures = 0;
+                    self.next_attempt = None;
+                }
+                Err(Error::Native(_, code, _)) if code == Errno::ENXIO as u32 => {
+                    self.failures = self.failures.saturating_add(1);
+                    self.next_attempt = Some(Instant::now() + self.backoff.delay(self.failures));
+                    return Ok(false);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        while let Some(message) = self.backlog.pop_front() {
+            if !self.send(&message)? {
+                self.backlog.push_front(message);
+                return Ok(false);
+            }
+            self.backlog_len -= message.len();
+        }
+        Ok(true)
+    }
+
+    /// Write a message to the reader, or disconnect if there is none.
+    /// Returns whether the message was written.
+    fn send(&mut self, message: &[u8]) -> Result<bool> {
+        let Some(pipe) = self.pipe.as_mut() else {
+            return Ok(false);
+        };
+        match pipe.write_all(message) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                self.disconnect();
+                self.next_attempt = None;
+                Ok(false)
+            }
+            Err(e) => Err(e)?,
+        }
+    }
+
+    /// Close the FIFO, since dropping a `Pipe` leaves its descriptors open
+    fn disconnect(&mut self) {
+        if let Some(pipe) = self.pipe.take() {
+            let _ = pipe.close();
+        }
+    }
+
+    /// Keep a message until a reader is connected, dropping the oldest ones
+    /// that no longer fit
+    fn keep(&mut self, message: &[u8]) {
+        self.backlog.push_back(message.to_vec());
+        self.backlog_len += message.len();
+        while self.backlog_len > self.max_bytes || self.backlog.len() > self.max_messages {
+            let Some(dropped) = self.backlog.pop_front() else {
+                break;
+            };
+            self.backlog_len -= dropped.len();
+            self.dropped += 1;
+        }
+    }
+}
+
+impl Write for ReconnectingWriter {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        let due = self
+            .next_attempt
+            .is_none_or(|next_attempt| Instant::now() >= next_attempt);
+        let connected = match self.pipe.is_some() || due {
+            true => self.reconnect()?,
+            false => false,
+        };
+        if !connected || !self.send(bytes)? {
+            self.keep(bytes);
+        }
+        Ok(bytes.len())
+    }
+
+    /// Replays the waiting messages if a reader is connected
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.pipe.is_some() {
+            self.reconnect()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ReconnectingWriter {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
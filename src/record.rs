@@ -0,0 +1,176 @@
+//! This module contains readers and writers for records ended by a sentinel
+//! delimiter, escaped so that payloads may contain the delimiter.
+
+use crate::{Error, Result};
+use std::io::{BufRead, ErrorKind, Write};
+
+/// The escape byte used unless another one is given: ASCII DLE
+pub const RECORD_ESCAPE: u8 = 0x10;
+
+/// Checks that records can be framed with a delimiter and escape byte
+fn check_framing(delimiter: &[u8], escape: u8) -> Result<()> {
+    match delimiter.first() {
+        None => Err(Error::Ipipe("Record delimiters cannot be empty")),
+        Some(&first) if first == escape => Err(Error::Ipipe(
+            "Record delimiters cannot start with the escape byte",
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Reads records written by a `RecordWriter`. Each record ends with a
+/// delimiter of one or more bytes, such as the `CANCEL` byte some streams use
+/// to mark their end since EOF is not observable on a pipe. Payload bytes
+/// equal to the escape byte or to the first byte of the delimiter are
+/// preceded by the escape byte, so records may contain the delimiter.
+///
+/// ```no_run
+/// use ipipe::{Pipe, RecordWriter};
+///
+/// const CANCEL: u8 = 24;
+///
+/// let pipe = Pipe::with_name("my_pipe").unwrap();
+/// let mut writer = RecordWriter::new(pipe.clone(), &[CANCEL]).unwrap();
+/// writer.write_record(&[1, CANCEL, 2]).unwrap();
+/// for record in pipe.records(&[CANCEL]).unwrap() {
+///     println!("{:?}", record.unwrap());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RecordReader<R> {
+    reader: R,
+    delimiter: Vec<u8>,
+    escape: u8,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    /// Read records ending with the given delimiter, escaped with
+    /// `RECORD_ESCAPE`
+    pub fn new(reader: R, delimiter: &[u8]) -> Result<Self> {
+        Self::with_escape(reader, delimiter, RECORD_ESCAPE)
+    }
+
+    /// Read records ending with the given delimiter, escaped with the given
+    /// byte. The delimiter cannot be empty or start with the escape byte.
+    pub fn with_escape(reader: R, delimiter: &[u8], escape: u8) -> Result<Self> {
+        check_framing(delimiter, escape)?;
+        Ok(RecordReader {
+            reader,
+            delimiter: delimiter.to_vec(),
+            escape,
+        })
+    }
+
+    /// Get the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read the next record without its delimiter, or `None` at the end of
+    /// the stream. A stream ending in the middle of a record is an
+    /// `UnexpectedEof` error.
+    pub fn read_record(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut record = vec![];
+        loop {
+            let byte = match self.next_byte()? {
+                Some(byte) => byte,
+                None if record.is_empty() => return Ok(None),
+                None => Err(std::io::Error::from(ErrorKind::UnexpectedEof))?,
+            };
+            if byte == self.escape {
+                match self.next_byte()? {
+                    Some(byte) => record.push(byte),
+                    None => Err(std::io::Error::from(ErrorKind::UnexpectedEof))?,
+                }
+            } else if byte == self.delimiter[0] {
+                for i in 1..self.delimiter.len() {
+                    if self.next_byte()? != Some(self.delimiter[i]) {
+                        Err(std::io::Error::new(
+                            ErrorKind::InvalidData,
+                            "Unescaped partial record delimiter",
+                        ))?;
+                    }
+                }
+                return Ok(Some(record));
+            } else {
+                record.push(byte);
+            }
+        }
+    }
+
+    fn next_byte(&mut self) -> std::io::Result<Option<u8>> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok([]) => return Ok(None),
+                Ok(available) => {
+                    let byte = available[0];
+                    self.reader.consume(1);
+                    return Ok(Some(byte));
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Writes records for a `RecordReader` to read. Each record is escaped and
+/// written along with its delimiter in a single write, so records of up to
+/// `PIPE_BUF` bytes once escaped do not interleave with other writers.
+#[derive(Debug)]
+pub struct RecordWriter<W> {
+    writer: W,
+    delimiter: Vec<u8>,
+    escape: u8,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Write records ending with the given delimiter, escaped with
+    /// `RECORD_ESCAPE`
+    pub fn new(writer: W, delimiter: &[u8]) -> Result<Self> {
+        Self::with_escape(writer, delimiter, RECORD_ESCAPE)
+    }
+
+    /// Write records ending with the given delimiter, escaped with the given
+    /// byte. The delimiter cannot be empty or start with the escape byte.
+    pub fn with_escape(writer: W, delimiter: &[u8], escape: u8) -> Result<Self> {
+        check_framing(delimiter, escape)?;
+        Ok(RecordWriter {
+            writer,
+            delimiter: delimiter.to_vec(),
+            escape,
+        })
+    }
+
+    /// Get a mutable reference to the underlying writer
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Get the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write a record followed by the delimiter
+    pub fn write_record(&mut self, payload: &[u8]) -> Result<()> {
+        let mut record = Vec::with_capacity(payload.len() + self.delimiter.len());
+        for &byte in payload {
+            if byte == self.escape || byte == self.delimiter[0] {
+                record.push(self.escape);
+            }
+            record.push(byte);
+        }
+        record.extend_from_slice(&self.delimiter);
+        self.writer.write_all(&record)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,328 @@
+//! A request/response protocol on top of `PipeServer` connections, so that a
+//! command-line tool can talk to a daemon through a named pipe.
+//!
+//! Every request carries an id, so a `Client` may be shared between threads
+//! with replies arriving in any order, and a `Server` handles requests on a
+//! bounded number of threads. Errors returned by the handler are sent back to
+//! the caller as `Error::Remote`.
+//!
+//! ```no_run
+//! use ipipe::rpc::{Client, Server};
+//!
+//! std::thread::spawn(|| {
+//!     let mut server = Server::with_name("my_daemon").unwrap();
+//!     server
+//!         .serve(|method, payload| match method {
+//!             "echo" => Ok(payload.to_vec()),
+//!             _ => Err(ipipe::Error::Ipipe("Unknown method")),
+//!         })
+//!         .unwrap();
+//! });
+//!
+//! let client = Client::with_name("my_daemon").unwrap();
+//! assert_eq!(b"Hello".to_vec(), client.call("echo", b"Hello").unwrap());
+//! ```
+
+use crate::{path_from_name, Error, Framed, PipeConnection, PipeServer, Result};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+const REQUEST: u8 = 0;
+const RESPONSE: u8 = 1;
+const ERROR: u8 = 2;
+
+/// Calls methods on a `Server`. Calls may be made from several threads at
+/// once.
+pub struct Client {
+    writer: Mutex<Framed<PipeConnection>>,
+    pending: Arc<Mutex<Pending>>,
+    next_id: AtomicU64,
+    timeout: Option<Duration>,
+}
+
+/// The calls waiting for a response
+#[derive(Default)]
+struct Pending {
+    calls: HashMap<u64, Sender<Result<Vec<u8>>>>,
+    closed: bool,
+}
+
+impl Client {
+    /// Connect to a server listening at the given path
+    pub fn connect(path: &Path) -> Result<Self> {
+        let connection = PipeConnection::connect(path)?;
+        let reader = Framed::new(connection.try_clone_reader()?);
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        {
+            let pending = pending.clone();
+            std::thread::Builder::new()
+                .name("ipipe-rpc-client".to_string())
+                .spawn(move || dispatch(reader, pending))?;
+        }
+        Ok(Client {
+            writer: Mutex::new(Framed::new(connection)),
+            pending,
+            next_id: AtomicU64::new(0),
+            timeout: None,
+        })
+    }
+
+    /// Connect to a server with the given name. Note that this is just a
+    /// string name, not a path.
+    pub fn with_name(name: &str) -> Result<Self> {
+        Client::connect(&path_from_name(name))
+    }
+
+    /// Give up on calls made with `call` after the given time, or never if
+    /// `None`, which is the default
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Call a method and wait for its response, for at most the timeout set
+    /// with `set_timeout`
+    pub fn call(&self, method: &str, payload: &[u8]) -> Result<Vec<u8>> {
+        self.call_with(method, payload, self.timeout)
+    }
+
+    /// Call a method and wait at most the given time for its response. The
+    /// call fails with `std::io::ErrorKind::TimedOut` if it does not arrive
+    /// in time.
+    pub fn call_timeout(&self, method: &str, payload: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+        self.call_with(method, payload, Some(timeout))
+    }
+
+    fn call_with(
+        &self,
+        method: &str,
+        payload: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>> {
+        let method_len =
+            u16::try_from(method.len()).map_err(|_| Error::Ipipe("Method name too long"))?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut request = header(id, REQUEST);
+        request.extend_from_slice(&method_len.to_be_bytes());
+        request.extend_from_slice(method.as_bytes());
+        request.extend_from_slice(payload);
+
+        let (sender, receiver) = mpsc::channel();
+        {
+            let mut pending = self.pending.lock()?;
+            if pending.closed {
+                Err(std::io::Error::from(ErrorKind::ConnectionAborted))?;
+            }
+            pending.calls.insert(id, sender);
+        }
+        if let Err(e) = self.writer.lock()?.write_frame(&request) {
+            self.pending.lock()?.calls.remove(&id);
+            Err(e)?;
+        }
+
+        let response = match timeout {
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(timeout) => receiver.recv_timeout(timeout),
+        };
+        match response {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                self.pending.lock()?.calls.remove(&id);
+                Err(std::io::Error::from(ErrorKind::TimedOut))?
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(std::io::Error::from(ErrorKind::ConnectionAborted))?
+            }
+        }
+    }
+}
+
+/// Hands responses to the calls waiting for them until the connection closes
+fn dispatch(mut reader: Framed<std::fs::File>, pending: Arc<Mutex<Pending>>) {
+    while let Ok(Some(frame)) = reader.read_frame() {
+        let Some((id, kind, body)) = parse_header(&frame) else {
+            continue;
+        };
+        let response = match kind {
+            RESPONSE => Ok(body.to_vec()),
+            ERROR => Err(Error::Remote(String::from_utf8_lossy(body).into_owned())),
+            _ => continue,
+        };
+        if let Some(sender) = pending.lock().unwrap().calls.remove(&id) {
+            let _ = sender.send(response);
+        }
+    }
+    let mut pending = pending.lock().unwrap();
+    pending.closed = true;
+    pending.calls.clear();
+}
+
+/// Serves method calls from `Client`s
+pub struct Server {
+    server: PipeServer,
+    max_clients: usize,
+    max_calls: usize,
+}
+
+impl Server {
+    /// Bind a server to the given path. See `PipeServer::bind`.
+    pub fn bind(path: &Path) -> Result<Self> {
+        Ok(Server {
+            server: PipeServer::bind(path)?,
+            max_clients: 64,
+            max_calls: 16,
+        })
+    }
+
+    /// Serve at most the given number of clients at once. Further clients
+    /// wait to be accepted until one disconnects. Defaults to 64.
+    pub fn max_clients(mut self, max_clients: usize) -> Self {
+        self.max_clients = max_clients.max(1);
+        self
+    }
+
+    /// Handle at most the given number of calls at once, across all clients.
+    /// Further calls wait for one to finish. Defaults to 16.
+    pub fn max_calls(mut self, max_calls: usize) -> Self {
+        self.max_calls = max_calls.max(1);
+        self
+    }
+
+    /// Bind a server with the given name. Note that this is just a string
+    /// name, not a path.
+    pub fn with_name(name: &str) -> Result<Self> {
+        Server::bind(&path_from_name(name))
+    }
+
+    /// Return the path clients connect to
+    pub fn path(&self) -> &Path {
+        self.server.path()
+    }
+
+    /// Accept clients and answer their calls with the handler, which is
+    /// given the method name and payload of each call. Each client is served
+    /// on its own thread, and each call is handled on its own thread, within
+    /// the limits of `max_clients` and `max_calls`. Clients that are refused
+    /// or cannot be served are skipped. Only returns if the registration FIFO
+    /// fails.
+    pub fn serve<H>(&mut self, handler: H) -> Result<()>
+    where
+        H: Fn(&str, &[u8]) -> Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        let clients = Arc::new(Slots::new(self.max_clients));
+        let calls = Arc::new(Slots::new(self.max_calls));
+        loop {
+            let client = clients.acquire();
+            let connection = match self.server.accept() {
+                Err(Error::Unauthorized(_)) => continue,
+                connection => connection?,
+            };
+            let handler = handler.clone();
+            let calls = calls.clone();
+            // A client that no thread can be spawned for is dropped
+            let _ = std::thread::Builder::new()
+                .name("ipipe-rpc-server".to_string())
+                .spawn(move || {
+                    let _client = client;
+                    serve_connection(connection, handler, calls)
+                });
+        }
+    }
+}
+
+/// A counting semaphore bounding the threads of a `Server`
+struct Slots {
+    free: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A taken slot, given back when dropped
+struct Slot(Arc<Slots>);
+
+impl Slots {
+    fn new(count: usize) -> Self {
+        Slots {
+            free: Mutex::new(count),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Block until a slot is free and take it
+    fn acquire(self: &Arc<Self>) -> Slot {
+        let mut free = self.free.lock().unwrap();
+        while *free == 0 {
+            free = self.released.wait(free).unwrap();
+        }
+        *free -= 1;
+        Slot(self.clone())
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap() += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// Answers the calls of one client until it disconnects
+fn serve_connection<H>(connection: PipeConnection, handler: Arc<H>, calls: Arc<Slots>) -> Result<()>
+where
+    H: Fn(&str, &[u8]) -> Result<Vec<u8>> + Send + Sync + 'static,
+{
+    let mut reader = Framed::new(connection.try_clone_reader()?);
+    let writer = Arc::new(Mutex::new(Framed::new(connection)));
+    while let Some(frame) = reader.read_frame()? {
+        let Some((id, REQUEST, body)) = parse_header(&frame) else {
+            continue;
+        };
+        let Some((method, payload)) = parse_request(body) else {
+            continue;
+        };
+        let (method, payload) = (method.to_string(), payload.to_vec());
+        let call = calls.acquire();
+        let handler = handler.clone();
+        let response_writer = writer.clone();
+        let spawned = std::thread::Builder::new()
+            .name("ipipe-rpc-call".to_string())
+            .spawn(move || {
+                let _call = call;
+                let response = match handler(&method, &payload) {
+                    Ok(body) => [header(id, RESPONSE), body].concat(),
+                    Err(e) => [header(id, ERROR), e.to_string().into_bytes()].concat(),
+                };
+                if let Ok(mut writer) = response_writer.lock() {
+                    let _ = writer.write_frame(&response);
+                }
+            });
+        if let Err(e) = spawned {
+            let response = [header(id, ERROR), e.to_string().into_bytes()].concat();
+            writer.lock()?.write_frame(&response)?;
+        }
+    }
+    Ok(())
+}
+
+fn header(id: u64, kind: u8) -> Vec<u8> {
+    let mut header = id.to_be_bytes().to_vec();
+    header.push(kind);
+    header
+}
+
+/// Splits a frame into its id, kind and body
+fn parse_header(frame: &[u8]) -> Option<(u64, u8, &[u8])> {
+    let id = u64::from_be_bytes(frame.get(..8)?.try_into().ok()?);
+    Some((id, *frame.get(8)?, &frame[9..]))
+}
+
+/// Splits the body of a request into its method name and payload
+fn parse_request(body: &[u8]) -> Option<(&str, &[u8])> {
+    let len = u16::from_be_bytes(body.get(..2)?.try_into().ok()?) as usize;
+    let method = std::str::from_utf8(body.get(2..2 + len)?).ok()?;
+    Some((method, &body[2 + len..]))
+}
@@ -0,0 +1,334 @@
+//! This module contains a selector for waiting on many pipes at once from a
+//! single thread. It uses epoll on Linux and Android, and poll elsewhere.
+
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::os::fd::RawFd;
+use std::time::Duration;
+
+use nix::poll::PollTimeout;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+use nix::poll::{PollFd, PollFlags};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use nix::sys::epoll::{Epoll, EpollCreateFlags, EpollEvent, EpollFlags};
+use std::os::fd::BorrowedFd;
+
+/// The readiness a pipe is registered for, or reported with. Values can be
+/// combined with `|`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Interest(u8);
+
+impl Interest {
+    pub const READABLE: Interest = Interest(0b001);
+    pub const WRITABLE: Interest = Interest(0b010);
+    pub const HANGUP: Interest = Interest(0b100);
+
+    pub fn is_readable(self) -> bool {
+        self.contains(Interest::READABLE)
+    }
+
+    pub fn is_writable(self) -> bool {
+        self.contains(Interest::WRITABLE)
+    }
+
+    pub fn is_hangup(self) -> bool {
+        self.contains(Interest::HANGUP)
+    }
+
+    /// No readiness at all
+    pub fn empty() -> Interest {
+        Interest(0)
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    fn contains(self, other: Interest) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for Interest {
+    type Output = Interest;
+
+    fn bitor(self, other: Interest) -> Interest {
+        Interest(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitAnd for Interest {
+    type Output = Interest;
+
+    fn bitand(self, other: Interest) -> Interest {
+        Interest(self.0 & other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Interest {
+    fn bitor_assign(&mut self, other: Interest) {
+        self.0 |= other.0
+    }
+}
+
+/// A readiness event returned by `Selector::select`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Event {
+    token: usize,
+    readiness: Interest,
+}
+
+impl Event {
+    /// The token the ready pipe was registered with
+    pub fn token(&self) -> usize {
+        self.token
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.readiness.is_readable()
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.readiness.is_writable()
+    }
+
+    /// Whether the other end of the pipe has gone away
+    pub fn is_hangup(&self) -> bool {
+        self.readiness.is_hangup()
+    }
+}
+
+/// Something that can be registered with a `Selector`
+pub trait Selectable {
+    /// Returns the file descriptors to wait on for the given interest,
+    /// opening any handle that has not been used yet. A pipe may use one
+    /// descriptor for reading and another for writing.
+    fn selector_fds(&mut self, interest: Interest) -> Result<Vec<(RawFd, Interest)>>;
+}
+
+/// Waits on many pipes at once. Registered pipes must stay open until they
+/// are deregistered.
+///
+/// ```no_run
+/// use ipipe::{Interest, Pipe, Selector};
+///
+/// let mut pipe = Pipe::with_name("selected").unwrap();
+/// let mut selector = Selector::new().unwrap();
+/// selector.register(&mut pipe, 7, Interest::READABLE).unwrap();
+/// for event in selector.select(None).unwrap() {
+///     assert_eq!(7, event.token());
+/// }
+/// ```
+pub struct Selector {
+    registrations: HashMap<usize, Vec<(RawFd, Interest)>>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    epoll: Epoll,
+}
+
+impl Selector {
+    /// Create an empty selector
+    pub fn new() -> Result<Self> {
+        Ok(Selector {
+            registrations: HashMap::new(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            epoll: Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC)?,
+        })
+    }
+
+    /// Register a pipe under the given token. Registering a token again
+    /// replaces its previous registration. A descriptor can only be
+    /// registered under one token at a time, and since the clones of a pipe
+    /// share its descriptors, registering a clone under another token than
+    /// the pipe errors.
+    pub fn register<S: Selectable + ?Sized>(
+        &mut self,
+        source: &mut S,
+        token: usize,
+        interest: Interest,
+    ) -> Result<()> {
+        let fds = combine_fds(source.selector_fds(interest)?);
+        let taken = fds.iter().any(|(fd, _)| {
+            self.token_of(*fd)
+                .is_some_and(|registered| registered != token)
+        });
+        if taken {
+            Err(Error::Ipipe("Pipe already registered under another token"))?;
+        }
+
+        self.deregister(token)?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        for (i, (fd, fd_interest)) in fds.iter().enumerate() {
+            let event = EpollEvent::new(epoll_flags(*fd_interest), *fd as u64);
+            if let Err(e) = self
+                .epoll
+                .add(unsafe { BorrowedFd::borrow_raw(*fd) }, event)
+            {
+                // Leave none of the pipe registered
+                for (added, _) in &fds[..i] {
+                    let _ = self.epoll.delete(unsafe { BorrowedFd::borrow_raw(*added) });
+                }
+                Err(e)?;
+            }
+        }
+        self.registrations.insert(token, fds);
+        Ok(())
+    }
+
+    /// Stop waiting on the pipe registered under the given token
+    pub fn deregister(&mut self, token: usize) -> Result<()> {
+        if let Some(_fds) = self.registrations.remove(&token) {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            for (fd, _) in _fds {
+                self.epoll.delete(unsafe { BorrowedFd::borrow_raw(fd) })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait until at least one registered pipe is ready, or until the
+    /// timeout elapses. A timeout of `None` waits forever. Returns one event
+    /// per ready token, which is empty if the timeout elapsed.
+    pub fn select(&mut self, timeout: Option<Duration>) -> Result<Vec<Event>> {
+        let timeout = timeout.map_or(PollTimeout::NONE, |timeout| {
+            PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX)
+        });
+        let mut events: Vec<Event> = vec![];
+        for (fd, ready) in self.wait(timeout)? {
+            let token = self.token_of(fd);
+            match events.iter_mut().find(|event| Some(event.token) == token) {
+                Some(event) => event.readiness |= ready,
+                None => events.extend(token.map(|token| Event {
+                    token,
+                    readiness: ready,
+                })),
+            }
+        }
+        Ok(events)
+    }
+
+    fn token_of(&self, fd: RawFd) -> Option<usize> {
+        self.registrations
+            .iter()
+            .find(|(_, fds)| fds.iter().any(|(registered, _)| *registered == fd))
+            .map(|(token, _)| *token)
+    }
+
+    fn interest_of(&self, fd: RawFd) -> Interest {
+        self.registrations
+            .values()
+            .flatten()
+            .find(|(registered, _)| *registered == fd)
+            .map_or(Interest::empty(), |(_, interest)| *interest)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn wait(&self, timeout: PollTimeout) -> Result<Vec<(RawFd, Interest)>> {
+        let len = self.registrations.values().map(Vec::len).sum::<usize>();
+        let mut events = vec![EpollEvent::empty(); len.max(1)];
+        let ready = match self.epoll.wait(&mut events, timeout) {
+            Ok(ready) => ready,
+            Err(nix::errno::Errno::EINTR) => 0,
+            Err(e) => Err(e)?,
+        };
+        Ok(events[..ready]
+            .iter()
+            .map(|event| {
+                let fd = event.data() as RawFd;
+                let flags = event.events();
+                let readiness = readiness(
+                    self.interest_of(fd),
+                    flags.contains(EpollFlags::EPOLLIN),
+                    flags.contains(EpollFlags::EPOLLOUT),
+                    flags.intersects(EpollFlags::EPOLLHUP | EpollFlags::EPOLLRDHUP),
+                    flags.contains(EpollFlags::EPOLLERR),
+                );
+                (fd, readiness)
+            })
+            .filter(|(_, readiness)| !readiness.is_empty())
+            .collect())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn wait(&self, timeout: PollTimeout) -> Result<Vec<(RawFd, Interest)>> {
+        let registered: Vec<(RawFd, Interest)> =
+            self.registrations.values().flatten().copied().collect();
+        let mut poll_fds: Vec<PollFd> = registered
+            .iter()
+            .map(|(fd, interest)| {
+                let mut flags = PollFlags::empty();
+                if interest.is_readable() || interest.is_hangup() {
+                    flags |= PollFlags::POLLIN;
+                }
+                if interest.is_writable() {
+                    flags |= PollFlags::POLLOUT;
+                }
+                PollFd::new(unsafe { BorrowedFd::borrow_raw(*fd) }, flags)
+            })
+            .collect();
+        match nix::poll::poll(&mut poll_fds, timeout) {
+            Ok(_) => {}
+            Err(nix::errno::Errno::EINTR) => return Ok(vec![]),
+            Err(e) => Err(e)?,
+        }
+        Ok(registered
+            .iter()
+            .zip(poll_fds.iter())
+            .map(|((fd, interest), poll_fd)| {
+                let flags = poll_fd.revents().unwrap_or(PollFlags::empty());
+                let readiness = readiness(
+                    *interest,
+                    flags.contains(PollFlags::POLLIN),
+                    flags.contains(PollFlags::POLLOUT),
+                    flags.contains(PollFlags::POLLHUP),
+                    flags.intersects(PollFlags::POLLERR | PollFlags::POLLNVAL),
+                );
+                (*fd, readiness)
+            })
+            .filter(|(_, readiness)| !readiness.is_empty())
+            .collect())
+    }
+}
+
+/// Merges the interests of pipes that read and write through one fd
+pub(crate) fn combine_fds(fds: Vec<(RawFd, Interest)>) -> Vec<(RawFd, Interest)> {
+    let mut combined: Vec<(RawFd, Interest)> = vec![];
+    for (fd, interest) in fds {
+        match combined.iter_mut().find(|(existing, _)| *existing == fd) {
+            Some((_, existing)) => *existing |= interest,
+            None => combined.push((fd, interest)),
+        }
+    }
+    combined
+}
+
+/// Translates raw readiness into the interests a descriptor asked for. A
+/// hangup or error also wakes readers and writers, so that their next call
+/// observes it.
+fn readiness(interest: Interest, read: bool, write: bool, hangup: bool, error: bool) -> Interest {
+    let mut readiness = Interest::empty();
+    if interest.is_readable() && (read || hangup || error) {
+        readiness |= Interest::READABLE;
+    }
+    if interest.is_writable() && (write || error) {
+        readiness |= Interest::WRITABLE;
+    }
+    if interest.is_hangup() && hangup {
+        readiness |= Interest::HANGUP;
+    }
+    readiness
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn epoll_flags(interest: Interest) -> EpollFlags {
+    let mut flags = EpollFlags::empty();
+    if interest.is_readable() {
+        flags |= EpollFlags::EPOLLIN;
+    }
+    if interest.is_writable() {
+        flags |= EpollFlags::EPOLLOUT;
+    }
+    if interest.is_hangup() {
+        flags |= EpollFlags::EPOLLRDHUP;
+    }
+    flags
+}
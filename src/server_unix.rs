@@ -0,0 +1,394 @@
+//! A listener/accept model built on top of FIFOs.
+//!
+//! Clients announce themselves by writing an id to a well-known registration
+//! FIFO. Each client owns a private pair of FIFOs next to the registration
+//! FIFO, `<path>.<id>.in` (client to server) and `<path>.<id>.out` (server to
+//! client), which the server opens when it accepts the client. Once it has
+//! opened its end of both FIFOs, the client writes a single `HELLO` byte, so
+//! that the server never waits on a client that went quiet.
+
+use super::{
+    path_from_name, Credentials, Error, Interest, OnCleanup, PeerPolicy, Pipe, PipeBuilder, Result,
+    Selectable,
+};
+use fcntl::OFlag;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::stat::{lstat, Mode, SFlag};
+use nix::sys::uio;
+use nix::{fcntl, unistd};
+use std::io::{BufRead, BufReader, IoSlice};
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// How long `PipeConnection::connect` waits for the server to accept
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the server waits for an accepted client to open its end
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The byte a client writes once it has opened its end of the connection
+const HELLO: u8 = 0x06;
+
+/// Accepts clients on a registration FIFO, handing out a private duplex
+/// connection per client.
+pub struct PipeServer {
+    registration: BufReader<Pipe>,
+    path: PathBuf,
+    policy: PeerPolicy,
+}
+
+impl PipeServer {
+    /// Bind a server to the registration FIFO at the given path, creating it
+    /// if it does not exist. The registration FIFO is deleted when the server
+    /// is dropped.
+    pub fn bind(path: &Path) -> Result<Self> {
+        PipeServer::bind_with(path, &PipeBuilder::new())
+    }
+
+    /// Bind a server using the peer policy of a `PipeBuilder`.
+    pub(crate) fn bind_with(path: &Path, builder: &PipeBuilder) -> Result<Self> {
+        Ok(PipeServer {
+            registration: BufReader::new(Pipe::open(path, OnCleanup::Delete)?),
+            path: path.to_path_buf(),
+            policy: builder.policy.clone(),
+        })
+    }
+
+    /// Bind a server with the given name. Note that this is just a string
+    /// name, not a path.
+    pub fn with_name(name: &str) -> Result<Self> {
+        PipeServer::bind(&path_from_name(name))
+    }
+
+    /// Return the path to the registration FIFO
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Block until a client registers, then return a connection to it.
+    /// Registrations from clients that went away before being accepted, or
+    /// that do not finish connecting within a second, are skipped. Clients
+    /// rejected by the peer policy are refused before anything is opened, so
+    /// that their `connect` fails, and reported as `Error::Unauthorized`.
+    /// Any other error comes from the registration FIFO itself.
+    pub fn accept(&mut self) -> Result<PipeConnection> {
+        loop {
+            // The registration FIFO is open for writing too, so reading
+            // blocks rather than hitting the end of the stream
+            let mut line = String::new();
+            self.registration.read_line(&mut line)?;
+            let id = line.trim_end_matches('\n');
+            if !is_valid_id(id) {
+                continue;
+            }
+
+            let (incoming, outgoing) = connection_paths(&self.path, id);
+            // The client created its FIFOs, so their owner identifies it
+            let Some(peer) = fifo_owner(&incoming, &outgoing) else {
+                continue;
+            };
+            // Removing the FIFOs of a refused client makes its connect fail
+            if let Err(e) = self.policy.check(peer) {
+                remove_fifos(&incoming, &outgoing);
+                break Err(e);
+            }
+            // Failing to open the FIFOs of one client, usually because it
+            // went away or never finished connecting, only skips that client
+            let Ok((reader, writer)) = open_client(&incoming, &outgoing) else {
+                remove_fifos(&incoming, &outgoing);
+                continue;
+            };
+            break Ok(PipeConnection {
+                reader,
+                writer,
+                paths: Some((incoming, outgoing)),
+                peer,
+                reported_pid: id.split('_').next().and_then(|pid| pid.parse().ok()),
+                nonblocking: false,
+                #[cfg(feature = "mio")]
+                mio_interest: Interest::empty(),
+            });
+        }
+    }
+
+    /// Returns an iterator over connections as they are accepted
+    pub fn incoming(&mut self) -> Incoming<'_> {
+        Incoming { server: self }
+    }
+}
+
+/// An iterator over the connections accepted by a `PipeServer`
+pub struct Incoming<'a> {
+    server: &'a mut PipeServer,
+}
+
+impl Iterator for Incoming<'_> {
+    type Item = Result<PipeConnection>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.server.accept())
+    }
+}
+
+/// One end of a private duplex connection between a `PipeServer` and a
+/// client. Dropping either end closes the connection, after which reads on
+/// the other end return 0 bytes.
+pub struct PipeConnection {
+    reader: OwnedFd,
+    writer: OwnedFd,
+    paths: Option<(PathBuf, PathBuf)>,
+    peer: Credentials,
+    reported_pid: Option<u32>,
+    nonblocking: bool,
+    #[cfg(feature = "mio")]
+    pub(crate) mio_interest: Interest,
+}
+
+impl PipeConnection {
+    /// Connect to a server listening on the registration FIFO at the given
+    /// path. Fails if no server is currently bound to it, or if it does not
+    /// accept within `DEFAULT_CONNECT_TIMEOUT`.
+    pub fn connect(path: &Path) -> Result<Self> {
+        PipeConnection::connect_timeout(path, DEFAULT_CONNECT_TIMEOUT)
+    }
+
+    /// Connect to a server, failing with `std::io::ErrorKind::TimedOut` if
+    /// it does not accept within the timeout.
+    pub fn connect_timeout(path: &Path, timeout: Duration) -> Result<Self> {
+        let deadline = Instant::now() + timeout;
+        let id = format!(
+            "{}_{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let (incoming, outgoing) = connection_paths(path, &id);
+        let mode = Mode::S_IWUSR | Mode::S_IRUSR;
+        let result = unistd::mkfifo(&incoming, mode)
+            .and_then(|_| unistd::mkfifo(&outgoing, mode))
+            .map_err(Error::from)
+            .and_then(|_| PipeConnection::register(path, &id, &incoming, &outgoing, deadline));
+        remove_fifos(&incoming, &outgoing);
+        result
+    }
+
+    /// Connect to a server with the given name. Note that this is just a
+    /// string name, not a path.
+    pub fn with_name(name: &str) -> Result<Self> {
+        PipeConnection::connect(&path_from_name(name))
+    }
+
+    fn register(
+        path: &Path,
+        id: &str,
+        incoming: &Path,
+        outgoing: &Path,
+        deadline: Instant,
+    ) -> Result<Self> {
+        let peer = Credentials::from_owner(path)?;
+        let reader = open_fd(outgoing, OFlag::O_RDONLY | OFlag::O_NONBLOCK)?;
+        {
+            let registration = open_fd(path, OFlag::O_WRONLY | OFlag::O_NONBLOCK)?;
+            unistd::write(registration.as_fd(), format!("{}\n", id).as_bytes())?;
+        }
+        // The server opens the incoming FIFO for reading once it accepts
+        let mut delay = Duration::from_millis(1);
+        let writer = loop {
+            match open_fd(incoming, OFlag::O_WRONLY | OFlag::O_NONBLOCK) {
+                Ok(writer) => break writer,
+                Err(Errno::ENXIO) if Instant::now() < deadline => {
+                    std::thread::sleep(
+                        delay.min(deadline.saturating_duration_since(Instant::now())),
+                    );
+                    delay = (delay * 2).min(Duration::from_millis(50));
+                }
+                Err(Errno::ENXIO) => Err(std::io::Error::from(std::io::ErrorKind::TimedOut))?,
+                // The server removed the FIFOs to refuse the connection
+                Err(Errno::ENOENT) => {
+                    Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))?
+                }
+                Err(e) => Err(e)?,
+            }
+        };
+        unistd::write(writer.as_fd(), &[HELLO])?;
+        set_blocking(&writer)?;
+        set_blocking(&reader)?;
+        Ok(PipeConnection {
+            reader,
+            writer,
+            paths: None,
+            peer,
+            reported_pid: None,
+            nonblocking: false,
+            #[cfg(feature = "mio")]
+            mio_interest: Interest::empty(),
+        })
+    }
+
+    /// Moves the connection into or out of non-blocking mode, where reads and
+    /// writes that cannot make progress fail with
+    /// `std::io::ErrorKind::WouldBlock`.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        for fd in [&self.reader, &self.writer] {
+            let flags = OFlag::from_bits_truncate(fcntl::fcntl(fd.as_raw_fd(), fcntl::F_GETFL)?);
+            let flags = if nonblocking {
+                flags | OFlag::O_NONBLOCK
+            } else {
+                flags & !OFlag::O_NONBLOCK
+            };
+            fcntl::fcntl(fd.as_raw_fd(), fcntl::F_SETFL(flags))?;
+        }
+        self.nonblocking = nonblocking;
+        Ok(())
+    }
+
+    /// Whether the connection is in non-blocking mode
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking
+    }
+
+    /// Duplicates the reading end of the connection, so that one thread can
+    /// read while another writes. The connection stays open until both are
+    /// dropped.
+    pub(crate) fn try_clone_reader(&self) -> Result<std::fs::File> {
+        Ok(self.reader.try_clone()?.into())
+    }
+
+    /// Returns the credentials of the peer. These are a best-effort guess
+    /// based on the owner of the FIFOs, and never include a pid.
+    pub fn peer_credentials(&self) -> Credentials {
+        self.peer
+    }
+
+    /// Returns the pid a client reported when registering, on the server
+    /// side of a connection. Clients choose it freely, so it is only a hint
+    /// and must not be used to authorize them.
+    pub fn reported_pid(&self) -> Option<u32> {
+        self.reported_pid
+    }
+}
+
+impl std::io::Write for PipeConnection {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        unistd::write(self.writer.as_fd(), bytes).map_err(std::io::Error::from)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        uio::writev(self.writer.as_fd(), bufs).map_err(std::io::Error::from)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl std::io::Read for PipeConnection {
+    fn read(&mut self, bytes: &mut [u8]) -> std::io::Result<usize> {
+        unistd::read(self.reader.as_raw_fd(), bytes).map_err(std::io::Error::from)
+    }
+}
+
+impl Selectable for PipeConnection {
+    fn selector_fds(&mut self, interest: Interest) -> Result<Vec<(RawFd, Interest)>> {
+        let mut fds = vec![];
+        let read_interest = interest & (Interest::READABLE | Interest::HANGUP);
+        if !read_interest.is_empty() {
+            fds.push((self.reader.as_raw_fd(), read_interest));
+        }
+        if interest.is_writable() {
+            fds.push((self.writer.as_raw_fd(), Interest::WRITABLE));
+        }
+        Ok(fds)
+    }
+}
+
+impl Drop for PipeConnection {
+    fn drop(&mut self) {
+        if let Some((incoming, outgoing)) = self.paths.take() {
+            remove_fifos(&incoming, &outgoing);
+        }
+    }
+}
+
+fn connection_paths(path: &Path, id: &str) -> (PathBuf, PathBuf) {
+    let mut incoming = path.as_os_str().to_owned();
+    incoming.push(format!(".{}.in", id));
+    let mut outgoing = path.as_os_str().to_owned();
+    outgoing.push(format!(".{}.out", id));
+    (incoming.into(), outgoing.into())
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// The owner of the FIFOs of a client, checked before they are opened. The
+/// paths must be FIFOs rather than symlinks or other files, and must have
+/// the same owner.
+fn fifo_owner(incoming: &Path, outgoing: &Path) -> Option<Credentials> {
+    let owner = |path: &Path| {
+        lstat(path)
+            .ok()
+            .filter(|stat| stat.st_mode & SFlag::S_IFMT.bits() == SFlag::S_IFIFO.bits())
+            .map(|stat| (stat.st_uid, stat.st_gid))
+    };
+    match (owner(incoming)?, owner(outgoing)?) {
+        ((uid, gid), outgoing) if outgoing == (uid, gid) => Some(Credentials {
+            uid,
+            gid,
+            pid: None,
+        }),
+        _ => None,
+    }
+}
+
+/// Opens the FIFOs of a client, waiting at most `HANDSHAKE_TIMEOUT` for it to
+/// open its end of the incoming FIFO and say `HELLO`. Fails with `ENXIO` if
+/// the client went away and `ETIMEDOUT` if it did not finish in time.
+fn open_client(incoming: &Path, outgoing: &Path) -> nix::Result<(OwnedFd, OwnedFd)> {
+    let flags = OFlag::O_NONBLOCK | OFlag::O_NOFOLLOW;
+    // The client holds the read end of its outgoing FIFO open before
+    // registering, so ENXIO here means it has already gone away.
+    let writer = open_fd(outgoing, OFlag::O_WRONLY | flags)?;
+    let reader = open_fd(incoming, OFlag::O_RDONLY | flags)?;
+    let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut poll_fd = [PollFd::new(reader.as_fd(), PollFlags::POLLIN)];
+        let timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+        match poll(&mut poll_fd, timeout) {
+            Ok(0) => return Err(Errno::ETIMEDOUT),
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(e) => return Err(e),
+        }
+        let mut hello = [0];
+        match unistd::read(reader.as_raw_fd(), &mut hello) {
+            Ok(1) if hello[0] == HELLO => break,
+            Err(Errno::EAGAIN | Errno::EINTR) => continue,
+            // The client closed its end, or is not speaking the protocol
+            _ => return Err(Errno::ENXIO),
+        }
+    }
+    set_blocking(&writer)?;
+    set_blocking(&reader)?;
+    Ok((reader, writer))
+}
+
+fn remove_fifos(incoming: &Path, outgoing: &Path) {
+    let _ = std::fs::remove_file(incoming);
+    let _ = std::fs::remove_file(outgoing);
+}
+
+fn open_fd(path: &Path, flags: OFlag) -> nix::Result<OwnedFd> {
+    fcntl::open(path, flags | OFlag::O_NOCTTY, Mode::empty())
+        .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn set_blocking(fd: &OwnedFd) -> nix::Result<()> {
+    let flags = OFlag::from_bits_truncate(fcntl::fcntl(fd.as_raw_fd(), fcntl::F_GETFL)?);
+    fcntl::fcntl(fd.as_raw_fd(), fcntl::F_SETFL(flags & !OFlag::O_NONBLOCK)).map(|_| ())
+}
@@ -1,13 +1,20 @@
-use crate::Pipe;
+use crate::{Pipe, PipeBuilder};
 use flurry::*;
 use lazy_static::lazy_static;
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use std::{io::Write, sync::Mutex};
 
 // FIXME: The inconsistent use of mutex should be cleaned up here
 
-// TODO: Accept non-stringly-typed keys somehow
 lazy_static! {
-    static ref PIPES: HashMap<String, Mutex<Pipe>> = HashMap::new();
+    static ref PIPES: PipeRegistry<String> = PipeRegistry::new();
 }
 
 /// Print a string to a static pipe
@@ -25,43 +32,920 @@ macro_rules! pprintln
     ($name:tt, $($arg:tt)*) => ($crate::print($name, {let mut s = format!($($arg)*); s.push('\n'); s}.as_str()))
 }
 
+/// What printing to a static pipe does when the pipe is full because nobody
+/// is reading from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Block the printing thread until there is room in the pipe
+    #[default]
+    Block,
+    /// Write what fits without blocking and drop the rest
+    DropNewest,
+    /// Keep up to the given number of bytes in an in-process ring buffer,
+    /// dropping the oldest messages when it overflows. Buffered messages are
+    /// written by later prints or by `flush`. For asynchronous static pipes,
+    /// the bytes queued for the writer thread are limited instead, on top of
+    /// the number of messages, and a message longer than the limit is dropped
+    /// right away.
+    DropOldest(usize),
+    /// Write what fits without blocking, and error with
+    /// `std::io::ErrorKind::WouldBlock` when nothing does
+    Error,
+}
+
+/// Statistics about the bytes printed to a static pipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PrintStats {
+    /// Bytes written into the pipe
+    pub written: u64,
+    /// Bytes dropped because the pipe was full
+    pub dropped: u64,
+    /// Bytes waiting in the ring buffer of `Overflow::DropOldest`
+    pub buffered: usize,
+}
+
+/// A description of a static pipe, as returned by `list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticPipeInfo<K> {
+    /// The key, or name, of the static pipe
+    pub key: K,
+    /// The path of the underlying pipe
+    pub path: PathBuf,
+    /// What printing does when the pipe is full
+    pub overflow: Overflow,
+    /// Whether the pipe is written by a background thread
+    pub asynchronous: bool,
+    /// The bytes written, dropped and buffered so far
+    pub stats: PrintStats,
+}
+
+type OnClose = Box<dyn FnOnce(&Path) + Send>;
+
+/// How long the writer thread of an asynchronous static pipe waits for room
+/// in the pipe before checking whether it should give up
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The bounded queue between printing threads and the writer thread of an
+/// asynchronous static pipe
+struct Queue {
+    state: Mutex<QueueState>,
+    capacity: usize,
+    // Signalled when a message is pushed or the queue is closed
+    pushed: Condvar,
+    // Signalled when a message is taken or written
+    popped: Condvar,
+    written: AtomicU64,
+    dropped: AtomicU64,
+}
+
+#[derive(Default)]
+struct QueueState {
+    messages: VecDeque<Vec<u8>>,
+    queued: usize,
+    writing: bool,
+    closed: bool,
+    // Set while flushing or closing under a policy that drops, so that the
+    // writer thread drops what is queued rather than wait for a reader
+    discard: bool,
+}
+
+impl Queue {
+    fn new(capacity: usize) -> Self {
+        Queue {
+            state: Mutex::new(QueueState::default()),
+            capacity: capacity.max(1),
+            pushed: Condvar::new(),
+            popped: Condvar::new(),
+            written: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue a message, applying the overflow policy when the queue is full.
+    /// Blocking gives up at the deadline, if any.
+    fn push(
+        &self,
+        message: &[u8],
+        overflow: Overflow,
+        deadline: Option<Instant>,
+    ) -> crate::Result<usize> {
+        let limit = match overflow {
+            Overflow::DropOldest(bytes) => bytes,
+            _ => usize::MAX,
+        };
+        // A message longer than the limit would only evict the others
+        if message.len() > limit {
+            self.drop_bytes(message.len());
+            return Ok(0);
+        }
+        let mut state = self.state.lock()?;
+        while state.messages.len() >= self.capacity
+            || state.queued.saturating_add(message.len()) > limit
+        {
+            match overflow {
+                Overflow::Block => match deadline {
+                    None => state = self.popped.wait(state)?,
+                    Some(deadline) => {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        if remaining.is_zero() {
+                            self.drop_bytes(message.len());
+                            Err(crate::Error::Io(std::io::ErrorKind::TimedOut.into()))?;
+                        }
+                        state = self.popped.wait_timeout(state, remaining)?.0;
+                    }
+                },
+                Overflow::DropNewest => {
+                    self.drop_bytes(message.len());
+                    return Ok(0);
+                }
+                Overflow::DropOldest(_) => {
+                    if let Some(oldest) = state.messages.pop_front() {
+                        state.queued -= oldest.len();
+                        self.drop_bytes(oldest.len());
+                    }
+                }
+                Overflow::Error => {
+                    self.drop_bytes(message.len());
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::WouldBlock,
+                        "Static pipe is full",
+                    ))?;
+                }
+            }
+        }
+        state.queued += message.len();
+        state.messages.push_back(message.to_vec());
+        self.pushed.notify_one();
+        Ok(message.len())
+    }
+
+    /// Write queued messages until the queue is closed and empty
+    fn run(&self, mut pipe: Pipe) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            match state.messages.pop_front() {
+                Some(message) => {
+                    state.queued -= message.len();
+                    state.writing = true;
+                    drop(state);
+                    self.popped.notify_all();
+                    let written = self.write(&mut pipe, &message);
+                    self.written.fetch_add(written as u64, Ordering::Relaxed);
+                    self.drop_bytes(message.len() - written);
+                    state = self.state.lock().unwrap();
+                    if written < message.len() && state.discard {
+                        for message in state.messages.drain(..) {
+                            self.drop_bytes(message.len());
+                        }
+                        state.queued = 0;
+                    }
+                    state.writing = false;
+                    self.popped.notify_all();
+                }
+                None if state.closed => break,
+                None => state = self.pushed.wait(state).unwrap(),
+            }
+        }
+    }
+
+    /// Write a message, giving up if the pipe fails or stays full while the
+    /// queue is being discarded. Returns how much was written.
+    fn write(&self, pipe: &mut Pipe, message: &[u8]) -> usize {
+        let mut written = 0;
+        while written < message.len() {
+            match pipe.write_timeout(&message[written..], RETRY_INTERVAL) {
+                Ok(len) => written += len,
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                    if self.state.lock().map_or(true, |state| state.discard) {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        written
+    }
+
+    /// Block until every queued message is written. If `discard` is set,
+    /// what is queued is dropped instead once the pipe stays full.
+    fn flush(&self, discard: bool) -> crate::Result<()> {
+        let mut state = self.state.lock()?;
+        state.discard = discard;
+        while !state.messages.is_empty() || state.writing {
+            state = self.popped.wait(state)?;
+        }
+        state.discard = false;
+        Ok(())
+    }
+
+    fn drop_bytes(&self, len: usize) {
+        self.dropped.fetch_add(len as u64, Ordering::Relaxed);
+    }
+}
+
+/// The writer thread of an asynchronous static pipe
+struct Writer {
+    queue: Arc<Queue>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Writer {
+    fn spawn(name: &str, pipe: Pipe, capacity: usize) -> crate::Result<Self> {
+        let queue = Arc::new(Queue::new(capacity));
+        let thread = {
+            let queue = queue.clone();
+            std::thread::Builder::new()
+                .name(format!("ipipe-{}", name))
+                .spawn(move || queue.run(pipe))?
+        };
+        Ok(Writer {
+            queue,
+            thread: Some(thread),
+        })
+    }
+
+    /// Write what is queued and stop the thread. If `discard` is set, what
+    /// is queued is dropped instead once the pipe stays full.
+    fn close(&mut self, discard: bool) {
+        if let Some(thread) = self.thread.take() {
+            if let Ok(mut state) = self.queue.state.lock() {
+                state.closed = true;
+                state.discard = discard;
+            }
+            self.queue.pushed.notify_all();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Writer {
+    /// Stops the thread without waiting for a reader
+    fn drop(&mut self) {
+        self.close(true);
+    }
+}
+
+/// A static pipe along with its overflow policy and statistics
+struct StaticPipe {
+    // Declared first so that the thread stops before the pipe is dropped
+    writer: Option<Writer>,
+    pipe: Pipe,
+    overflow: Overflow,
+    backlog: VecDeque<Vec<u8>>,
+    backlog_len: usize,
+    // Bytes of the front message of the backlog that were already written
+    sent: usize,
+    written: u64,
+    dropped: u64,
+    on_close: Vec<OnClose>,
+    closed: bool,
+}
+
+impl StaticPipe {
+    fn new(pipe: Pipe) -> Self {
+        StaticPipe {
+            writer: None,
+            pipe,
+            overflow: Overflow::Block,
+            backlog: VecDeque::new(),
+            backlog_len: 0,
+            sent: 0,
+            written: 0,
+            dropped: 0,
+            on_close: vec![],
+            closed: false,
+        }
+    }
+
+    fn print(&mut self, bytes: &[u8]) -> crate::Result<usize> {
+        self.write(bytes, None)
+    }
+
+    /// Write following the overflow policy. Blocking gives up at the
+    /// deadline, if any, and what was not written by then is dropped.
+    fn write(&mut self, bytes: &[u8], deadline: Option<Instant>) -> crate::Result<usize> {
+        if self.closed {
+            Err(crate::Error::Ipipe("Pipe not initialized"))?;
+        }
+        if let Some(writer) = self.writer.as_ref() {
+            return writer.queue.push(bytes, self.overflow, deadline);
+        }
+        if !matches!(self.overflow, Overflow::DropOldest(_)) && !self.finish(deadline)? {
+            // The rest of the message is still in the way
+            self.dropped += bytes.len() as u64;
+            return match self.overflow {
+                Overflow::DropNewest => Ok(0),
+                Overflow::Error => Err(std::io::Error::new(
+                    std::io::ErrorKind::WouldBlock,
+                    "Static pipe is full",
+                ))?,
+                _ => Err(crate::Error::Io(std::io::ErrorKind::TimedOut.into())),
+            };
+        }
+        match self.overflow {
+            Overflow::Block => {
+                let written = match deadline {
+                    None => self.pipe.write(bytes)?,
+                    Some(deadline) => write_before(&mut self.pipe, bytes, deadline)?,
+                };
+                self.written += written as u64;
+                self.dropped += (bytes.len() - written) as u64;
+                match written {
+                    0 if !bytes.is_empty() => {
+                        Err(crate::Error::Io(std::io::ErrorKind::TimedOut.into()))
+                    }
+                    written => Ok(written),
+                }
+            }
+            Overflow::DropNewest => {
+                let written = self.write_now(bytes)?;
+                self.dropped += (bytes.len() - written) as u64;
+                Ok(written)
+            }
+            Overflow::Error => match self.write_now(bytes)? {
+                0 if !bytes.is_empty() => {
+                    self.dropped += bytes.len() as u64;
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::WouldBlock,
+                        "Static pipe is full",
+                    ))?
+                }
+                written => {
+                    self.dropped += (bytes.len() - written) as u64;
+                    Ok(written)
+                }
+            },
+            Overflow::DropOldest(capacity) => {
+                self.backlog.push_back(bytes.to_vec());
+                self.backlog_len += bytes.len();
+                self.drain(deadline.unwrap_or_else(Instant::now))?;
+                self.evict(capacity);
+                Ok(bytes.len())
+            }
+        }
+    }
+
+    /// Write a whole record, or drop it whole, without blocking past the
+    /// deadline. A record of up to `PIPE_BUF` bytes is written atomically,
+    /// while a longer one is only written if the pipe has room for all of
+    /// it. Returns whether the record was written or queued.
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    fn write_record(&mut self, record: &[u8], deadline: Instant) -> crate::Result<bool> {
+        if self.writer.is_some() || matches!(self.overflow, Overflow::DropOldest(_)) {
+            // Queued and buffered messages are only written or dropped whole
+            return match self.write(record, Some(deadline)) {
+                Ok(written) => Ok(written == record.len()),
+                Err(crate::Error::Io(e)) if e.kind() == std::io::ErrorKind::TimedOut => Ok(false),
+                Err(e) => Err(e),
+            };
+        }
+        if self.closed {
+            Err(crate::Error::Ipipe("Pipe not initialized"))?;
+        }
+        if !self.finish(Some(deadline))?
+            || record.len() > crate::PIPE_BUF && !has_room(&self.pipe, record.len())
+        {
+            self.dropped += record.len() as u64;
+            return Ok(false);
+        }
+        // Only another writer filling the pipe meanwhile can cut it short
+        let written = write_before(&mut self.pipe, record, deadline)?;
+        self.written += written as u64;
+        self.dropped += (record.len() - written) as u64;
+        Ok(written == record.len())
+    }
+
+    /// Write without blocking, returning how much was written
+    fn write_now(&mut self, bytes: &[u8]) -> crate::Result<usize> {
+        let written = write_before(&mut self.pipe, bytes, Instant::now())?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    /// Write as much of the backlog as fits until the deadline
+    fn drain(&mut self, deadline: Instant) -> crate::Result<()> {
+        while let Some(message) = self.backlog.front() {
+            let message = &message[self.sent..];
+            let written = write_before(&mut self.pipe, message, deadline)?;
+            self.written += written as u64;
+            self.sent += written;
+            if written < message.len() {
+                break;
+            }
+            self.backlog_len -= self.sent;
+            self.sent = 0;
+            self.backlog.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Finish writing the message left partly written by
+    /// `Overflow::DropOldest`, so that messages written under another policy
+    /// do not tear it. Blocking gives up at the deadline, if any, and does not
+    /// happen at all under policies that drop. Returns whether the message
+    /// is done.
+    fn finish(&mut self, deadline: Option<Instant>) -> crate::Result<bool> {
+        if self.backlog.is_empty() {
+            return Ok(true);
+        }
+        match deadline {
+            None if self.overflow == Overflow::Block => self.flush()?,
+            deadline => self.drain(deadline.unwrap_or_else(Instant::now))?,
+        }
+        Ok(self.backlog.is_empty())
+    }
+
+    /// Drop the oldest messages until the backlog fits the capacity. A
+    /// partially written message is kept so the reader never sees it cut
+    /// short.
+    fn evict(&mut self, capacity: usize) {
+        let first = if self.sent > 0 { 1 } else { 0 };
+        while self.backlog_len > capacity {
+            match self.backlog.remove(first) {
+                Some(message) => {
+                    self.backlog_len -= message.len();
+                    self.dropped += message.len() as u64;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Block until the whole backlog or queue is written
+    fn flush(&mut self) -> crate::Result<()> {
+        if self.closed {
+            Err(crate::Error::Ipipe("Pipe not initialized"))?;
+        }
+        if let Some(writer) = self.writer.as_ref() {
+            return writer.queue.flush(self.overflow != Overflow::Block);
+        }
+        while let Some(message) = self.backlog.pop_front() {
+            self.pipe.write_all(&message[self.sent..])?;
+            self.written += (message.len() - self.sent) as u64;
+            self.backlog_len -= message.len();
+            self.sent = 0;
+        }
+        Ok(())
+    }
+
+    /// Write what is queued, close the pipe, deleting it if its cleanup
+    /// policy says so, and run the close callbacks
+    fn close(&mut self) {
+        if self.closed {
+            return;
+        }
+        if let Some(mut writer) = self.writer.take() {
+            writer.close(self.overflow != Overflow::Block);
+        }
+        self.closed = true;
+        let pipe = std::mem::take(&mut self.pipe);
+        let path = pipe.path().to_path_buf();
+        let _ = pipe.close();
+        for on_close in self.on_close.drain(..) {
+            on_close(&path);
+        }
+    }
+
+    fn stats(&self) -> PrintStats {
+        let mut stats = PrintStats {
+            written: self.written,
+            dropped: self.dropped,
+            buffered: self.backlog_len - self.sent,
+        };
+        if let Some(writer) = self.writer.as_ref() {
+            let queue = &writer.queue;
+            stats.written += queue.written.load(Ordering::Relaxed);
+            stats.dropped += queue.dropped.load(Ordering::Relaxed);
+            stats.buffered += queue.state.lock().unwrap().queued;
+        }
+        stats
+    }
+}
+
+/// Whether a pipe has room for the given number of bytes, going by the
+/// capacity and unread bytes it reports. The kernel hands out room in pages,
+/// and the unread bytes may straddle two more pages than they fill, so the
+/// estimate errs on the side of too little room. Pipes that do not report
+/// their capacity are assumed to be full.
+#[cfg(all(unix, any(feature = "log", feature = "tracing")))]
+fn has_room(pipe: &Pipe, len: usize) -> bool {
+    let page = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        page if page > 0 => page as usize,
+        _ => crate::PIPE_BUF,
+    };
+    match (pipe.capacity(), pipe.bytes_available()) {
+        (Ok(capacity), Ok(unread)) => capacity.saturating_sub((unread / page + 2) * page) >= len,
+        _ => false,
+    }
+}
+
+/// Whether a pipe has room for the given number of bytes. Writes on Windows
+/// are not bounded by a timeout, see `Pipe::write_timeout`, so they are never
+/// cut short either.
+#[cfg(all(windows, any(feature = "log", feature = "tracing")))]
+fn has_room(_: &Pipe, _: usize) -> bool {
+    true
+}
+
+/// Write to a pipe until the deadline, returning how much was written
+fn write_before(pipe: &mut Pipe, bytes: &[u8], deadline: Instant) -> crate::Result<usize> {
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    match pipe.write_timeout(bytes, timeout) {
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(0),
+        written => Ok(written?),
+    }
+}
+
+/// A set of static pipes. Keys name the pipe they refer to through `AsRef<str>`,
+/// so enums and `&'static str` constants can be used in place of strings. The
+/// free functions of this module, as well as `pprint!` and `pprintln!`, use a
+/// global `PipeRegistry<String>`.
+///
+/// ```no_run
+/// use ipipe::{OnCleanup, PipeBuilder, PipeRegistry};
+///
+/// #[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+/// enum Channel {
+///     Debug,
+///     Metrics,
+/// }
+///
+/// impl AsRef<str> for Channel {
+///     fn as_ref(&self) -> &str {
+///         match self {
+///             Channel::Debug => "my_debug",
+///             Channel::Metrics => "my_metrics",
+///         }
+///     }
+/// }
+///
+/// let registry = PipeRegistry::new();
+/// let debug = registry.init(Channel::Debug).unwrap();
+/// let metrics = registry
+///     .init_with(Channel::Metrics, PipeBuilder::new().on_cleanup(OnCleanup::Delete))
+///     .unwrap();
+/// registry.print(&Channel::Debug, "Hello\n").unwrap();
+/// ```
+pub struct PipeRegistry<K: 'static + Hash + Ord + Clone + Send + Sync> {
+    pipes: HashMap<K, Mutex<StaticPipe>>,
+}
+
+impl<K> Default for PipeRegistry<K>
+where
+    K: 'static + AsRef<str> + Hash + Ord + Clone + Send + Sync,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> PipeRegistry<K>
+where
+    K: 'static + AsRef<str> + Hash + Ord + Clone + Send + Sync,
+{
+    /// Create an empty registry
+    pub fn new() -> Self {
+        PipeRegistry {
+            pipes: HashMap::new(),
+        }
+    }
+
+    /// Initialize a static pipe named after the key and return a handle to
+    /// it. A static pipe already initialized with the same key is replaced,
+    /// and closed once the new one is in place.
+    pub fn init(&self, key: K) -> crate::Result<Pipe> {
+        self.init_with(key, PipeBuilder::new())
+    }
+
+    /// Initialize a static pipe named after the key using the options of a
+    /// `PipeBuilder`, and return a handle to it.
+    pub fn init_with(&self, key: K, builder: PipeBuilder) -> crate::Result<Pipe> {
+        let pipe = builder.with_name(key.as_ref())?;
+        let reader = pipe.clone();
+        self.replace(key, StaticPipe::new(pipe));
+        Ok(reader)
+    }
+
+    /// Initialize a static pipe written by a background thread, and return a
+    /// handle to it. Printing queues the message for the thread instead of
+    /// writing it, and the overflow policy applies when more than `capacity`
+    /// messages are queued. `flush` and `close` wait for the queue to be
+    /// written, except under a policy other than `Overflow::Block`, where
+    /// they drop what is left once the pipe stays full, so that they do not
+    /// hang when nobody reads.
+    /// As with `init`, a static pipe already initialized with the same key is
+    /// replaced.
+    pub fn init_async(&self, key: K, capacity: usize) -> crate::Result<Pipe> {
+        self.init_async_with(key, capacity, PipeBuilder::new())
+    }
+
+    /// Initialize a static pipe written by a background thread using the
+    /// options of a `PipeBuilder`, and return a handle to it. See
+    /// `init_async`.
+    pub fn init_async_with(
+        &self,
+        key: K,
+        capacity: usize,
+        builder: PipeBuilder,
+    ) -> crate::Result<Pipe> {
+        let pipe = builder.with_name(key.as_ref())?;
+        let reader = pipe.clone();
+        let writer = Writer::spawn(key.as_ref(), pipe.clone(), capacity)?;
+        let mut pipe = StaticPipe::new(pipe);
+        pipe.writer = Some(writer);
+        self.replace(key, pipe);
+        Ok(reader)
+    }
+
+    /// Put a static pipe in place of the one with the same key, if any, so
+    /// that prints never find the key missing, and close the old one
+    fn replace(&self, key: K, pipe: StaticPipe) {
+        let path = pipe.pipe.path().to_path_buf();
+        let guard = self.pipes.guard();
+        if let Some(old) = self.pipes.insert(key, Mutex::from(pipe), &guard) {
+            if let Ok(mut old) = old.lock() {
+                // Deleting the path would take the new pipe along with it
+                if old.pipe.path() == path {
+                    old.pipe.keep_path();
+                }
+                old.close();
+            }
+        }
+    }
+
+    /// Get a handle to an existing static pipe
+    pub fn get<Q>(&self, key: &Q) -> Option<Pipe>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        self.pipes
+            .get(key, &self.pipes.guard())
+            .map(|pipe| pipe.lock().unwrap())
+            .filter(|pipe| !pipe.closed)
+            .map(|pipe| pipe.pipe.clone())
+    }
+
+    /// Whether a static pipe is initialized
+    pub fn is_initialized<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        self.pipes
+            .get(key, &self.pipes.guard())
+            .is_some_and(|pipe| !pipe.lock().unwrap().closed)
+    }
+
+    /// Describe every static pipe, sorted by key
+    pub fn list(&self) -> Vec<StaticPipeInfo<K>> {
+        let guard = self.pipes.guard();
+        let mut list: Vec<_> = self
+            .pipes
+            .iter(&guard)
+            .map(|(key, pipe)| (key, pipe.lock().unwrap()))
+            .filter(|(_, pipe)| !pipe.closed)
+            .map(|(key, pipe)| StaticPipeInfo {
+                key: key.clone(),
+                path: pipe.pipe.path().to_path_buf(),
+                overflow: pipe.overflow,
+                asynchronous: pipe.writer.is_some(),
+                stats: pipe.stats(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.key.cmp(&b.key));
+        list
+    }
+
+    /// Call a function with the path of a static pipe once it is closed
+    pub fn on_close<Q, F>(&self, key: &Q, on_close: F) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+        F: FnOnce(&Path) + Send + 'static,
+    {
+        match self.pipes.get(key, &self.pipes.guard()) {
+            None => Err(crate::Error::Ipipe("Pipe not initialized")),
+            Some(pipe) => {
+                pipe.lock()?.on_close.push(Box::new(on_close));
+                Ok(())
+            }
+        }
+    }
+
+    /// Closes a static pipe, waiting for anything queued to be written, and
+    /// deleting the pipe if it was initialized with `OnCleanup::Delete`
+    pub fn close<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        let guard = self.pipes.guard();
+        if let Some(pipe) = self.pipes.remove(key, &guard) {
+            close_pipe(pipe);
+        }
+    }
+
+    /// Closes all static pipes
+    pub fn close_all(&self) {
+        let guard = self.pipes.guard();
+        for pipe in self.pipes.values(&guard) {
+            close_pipe(pipe);
+        }
+        self.pipes.clear(&guard)
+    }
+
+    /// The lowest-level static-pipe print function. Errors if the pipe is not
+    /// initialized.
+    #[inline]
+    pub fn print<Q>(&self, key: &Q, s: &str) -> crate::Result<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        self.write(key, s.as_bytes())
+    }
+
+    /// Write bytes to a static pipe, following its overflow policy like
+    /// `print`. Errors if the pipe is not initialized.
+    pub fn write<Q>(&self, key: &Q, bytes: &[u8]) -> crate::Result<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        match self.pipes.get(key, &self.pipes.guard()) {
+            None => Err(crate::Error::Ipipe("Pipe not initialized")),
+            Some(pipe) => pipe.lock()?.print(bytes),
+        }
+    }
+
+    /// Write to a static pipe without blocking for longer than the timeout,
+    /// following its overflow policy like `write`. What was not written or
+    /// queued by then is dropped. See `Pipe::write_timeout`.
+    pub fn write_timeout<Q>(&self, key: &Q, bytes: &[u8], timeout: Duration) -> crate::Result<usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        match self.pipes.get(key, &self.pipes.guard()) {
+            None => Err(crate::Error::Ipipe("Pipe not initialized")),
+            Some(pipe) => pipe.lock()?.write(bytes, Some(Instant::now() + timeout)),
+        }
+    }
+
+    /// Write a whole record to a static pipe within the timeout, or drop it
+    /// whole, whatever its overflow policy. Returns whether the record was
+    /// written. Used by the `log` and `tracing` integrations.
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    pub(crate) fn write_record<Q>(&self, key: &Q, record: &[u8], timeout: Duration) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        match self.pipes.get(key, &self.pipes.guard()) {
+            None => false,
+            Some(pipe) => pipe.lock().is_ok_and(|mut pipe| {
+                pipe.write_record(record, Instant::now() + timeout)
+                    .unwrap_or(false)
+            }),
+        }
+    }
+
+    /// Set what printing does when a static pipe is full. Switching away from
+    /// `Overflow::DropOldest` writes what it can of the ring buffer and drops
+    /// the rest, except for a message already partly written, which is
+    /// finished before anything else is written.
+    pub fn set_overflow<Q>(&self, key: &Q, overflow: Overflow) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        match self.pipes.get(key, &self.pipes.guard()) {
+            None => Err(crate::Error::Ipipe("Pipe not initialized")),
+            Some(pipe) => {
+                let mut pipe = pipe.lock()?;
+                pipe.overflow = overflow;
+                match overflow {
+                    Overflow::DropOldest(capacity) => pipe.evict(capacity),
+                    _ => {
+                        pipe.drain(Instant::now())?;
+                        pipe.evict(0);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Block until everything buffered for a static pipe is written
+    pub fn flush<Q>(&self, key: &Q) -> crate::Result<()>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        match self.pipes.get(key, &self.pipes.guard()) {
+            None => Err(crate::Error::Ipipe("Pipe not initialized")),
+            Some(pipe) => pipe.lock()?.flush(),
+        }
+    }
+
+    /// Get the statistics of a static pipe
+    pub fn stats<Q>(&self, key: &Q) -> Option<PrintStats>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        self.pipes
+            .get(key, &self.pipes.guard())
+            .map(|pipe| pipe.lock().unwrap().stats())
+    }
+}
+
+/// Closes a removed static pipe right away, since the map only drops removed
+/// values once no other thread can be reading them
+fn close_pipe(pipe: &Mutex<StaticPipe>) {
+    if let Ok(mut pipe) = pipe.lock() {
+        pipe.close();
+    }
+}
+
+/// The global registry used by the free functions of this module
+pub fn registry() -> &'static PipeRegistry<String> {
+    &PIPES
+}
+
 /// Initialize a static pipe and return a handle to it.
 pub fn init(name: &str) -> crate::Result<Pipe> {
-    let pipe = Pipe::with_name(name)?;
-    let reader = pipe.clone();
-    PIPES.insert(name.to_string(), Mutex::from(pipe), &PIPES.guard());
-    Ok(reader)
+    PIPES.init(name.to_string())
+}
+
+/// Initialize a static pipe using the options of a `PipeBuilder` and return a
+/// handle to it.
+pub fn init_with(name: &str, builder: PipeBuilder) -> crate::Result<Pipe> {
+    PIPES.init_with(name.to_string(), builder)
+}
+
+/// Initialize a static pipe written by a background thread, and return a
+/// handle to it. See `PipeRegistry::init_async`.
+pub fn init_async(name: &str, capacity: usize) -> crate::Result<Pipe> {
+    PIPES.init_async(name.to_string(), capacity)
+}
+
+/// Initialize a static pipe written by a background thread using the options
+/// of a `PipeBuilder`, and return a handle to it.
+pub fn init_async_with(name: &str, capacity: usize, builder: PipeBuilder) -> crate::Result<Pipe> {
+    PIPES.init_async_with(name.to_string(), capacity, builder)
 }
 
 /// Get a handle to an existing static pipe
 pub fn get(name: &str) -> Option<Pipe> {
-    PIPES
-        .get(name, &PIPES.guard())
-        .map(|pipe| pipe.lock().unwrap().clone())
+    PIPES.get(name)
+}
+
+/// Set what printing does when a static pipe is full
+pub fn set_overflow(name: &str, overflow: Overflow) -> crate::Result<()> {
+    PIPES.set_overflow(name, overflow)
+}
+
+/// Block until everything buffered for a static pipe is written
+pub fn flush(name: &str) -> crate::Result<()> {
+    PIPES.flush(name)
 }
 
-/// Closes a static pipe
+/// Get the statistics of a static pipe
+pub fn stats(name: &str) -> Option<PrintStats> {
+    PIPES.stats(name)
+}
+
+/// Whether a static pipe is initialized
+pub fn is_initialized(name: &str) -> bool {
+    PIPES.is_initialized(name)
+}
+
+/// Describe every static pipe, sorted by name
+pub fn list() -> Vec<StaticPipeInfo<String>> {
+    PIPES.list()
+}
+
+/// Call a function with the path of a static pipe once it is closed
+pub fn on_close<F: FnOnce(&Path) + Send + 'static>(name: &str, on_close: F) -> crate::Result<()> {
+    PIPES.on_close(name, on_close)
+}
+
+/// Closes a static pipe, waiting for anything queued to be written, and
+/// deleting the pipe if it was initialized with `OnCleanup::Delete`
 pub fn close(name: &str) {
-    PIPES.remove(name, &PIPES.guard());
+    PIPES.close(name)
 }
 
 /// Closes all static pipes
 pub fn close_all() {
-    PIPES.clear(&PIPES.guard())
+    PIPES.close_all()
 }
 
-/// The lowest-level static-pipe print function. Panics if pipe is not
+/// The lowest-level static-pipe print function. Errors if the pipe is not
 /// initialized.
 #[inline]
 pub fn print(name: &str, s: &str) -> crate::Result<usize> {
-    match PIPES.get(name, &PIPES.guard()) {
-        None => Err(crate::Error::Ipipe("Pipe not initialized")),
-        Some(pipe) => {
-            let mut pipe = pipe.lock()?;
-            match pipe.write(s.as_bytes()) {
-                Ok(written) => Ok(written),
-                Err(e) => Err(crate::Error::from(e)),
-            }
-        }
-    }
+    PIPES.print(name, s)
 }
@@ -123,3 +123,1212 @@ fn test_name() {
     let pipe = Pipe::with_name("test_name").unwrap();
     assert_eq!(pipe.name().unwrap(), "test_name");
 }
+
+#[cfg(unix)]
+#[test]
+fn test_server() -> crate::Result<()> {
+    use crate::{PipeConnection, PipeServer};
+    use std::io::{BufRead, BufReader};
+
+    let mut server = PipeServer::with_name("test_server")?;
+    let thread = thread::spawn(move || -> crate::Result<()> {
+        for connection in server.incoming().take(2) {
+            let connection = connection?;
+            assert_eq!(Some(std::process::id()), connection.reported_pid());
+            let mut connection = BufReader::new(connection);
+            let mut line = String::new();
+            connection.read_line(&mut line)?;
+            write!(connection.get_mut(), "echo: {}", line)?;
+        }
+        Ok(())
+    });
+
+    for i in 0..2 {
+        let mut client = BufReader::new(PipeConnection::with_name("test_server")?);
+        writeln!(client.get_mut(), "client {}", i)?;
+        let mut line = String::new();
+        client.read_line(&mut line)?;
+        assert_eq!(format!("echo: client {}\n", i), line);
+    }
+    thread.join().unwrap()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_connect_timeout() -> crate::Result<()> {
+    use crate::{PipeConnection, PipeServer};
+    use std::time::Duration;
+
+    // The server never accepts, so the client gives up and cleans up
+    let server = PipeServer::with_name("test_connect_timeout")?;
+    let err = PipeConnection::connect_timeout(server.path(), Duration::from_millis(50))
+        .err()
+        .unwrap();
+    assert_eq!(
+        std::io::ErrorKind::TimedOut,
+        std::io::Error::from(err).kind()
+    );
+    let prefix = format!("{}.", server.path().file_name().unwrap().to_string_lossy());
+    let leftovers = std::fs::read_dir(server.path().parent().unwrap())?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .count();
+    assert_eq!(0, leftovers);
+    Ok(())
+}
+
+#[cfg(feature = "uds")]
+#[test]
+fn test_socket() -> crate::Result<()> {
+    use crate::{OnCleanup, PipeBuilder, PipeKind};
+    use std::io::{BufRead, BufReader};
+
+    let builder = PipeBuilder::new()
+        .kind(PipeKind::Socket)
+        .on_cleanup(OnCleanup::Delete);
+    let server = builder.with_name("test_socket")?;
+    let mut client = builder.with_name("test_socket")?;
+
+    // The clone accepts the connection on behalf of the original
+    let mut reader = BufReader::new(server.clone());
+    let thread = thread::spawn(move || -> std::io::Result<String> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        Ok(line)
+    });
+    writeln!(client, "ping")?;
+    assert_eq!("ping\n", thread.join().unwrap()?);
+
+    let mut server = server;
+    writeln!(server, "pong")?;
+    let mut line = String::new();
+    BufReader::new(client).read_line(&mut line)?;
+    assert_eq!("pong\n", line);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_server_policy() -> crate::Result<()> {
+    use crate::{Error, PipeBuilder, PipeConnection};
+
+    let uid = nix::unistd::geteuid().as_raw();
+    let mut server = PipeBuilder::new()
+        .allow_uids([uid + 1])
+        .server_with_name("test_server_policy")?;
+    let thread = thread::spawn(move || server.accept().map(|_| ()));
+
+    // The rejected client is refused rather than left waiting
+    let err = PipeConnection::with_name("test_server_policy")
+        .err()
+        .unwrap();
+    assert_eq!(
+        std::io::ErrorKind::ConnectionRefused,
+        std::io::Error::from(err).kind()
+    );
+    match thread.join().unwrap() {
+        Err(Error::Unauthorized(creds)) => {
+            assert_eq!(uid, creds.uid);
+            // The pid is chosen by the client, so it is not a credential
+            assert_eq!(None, creds.pid);
+        }
+        _ => panic!("Expected the client to be rejected"),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_server_stalled_client() -> crate::Result<()> {
+    use crate::{PipeConnection, PipeServer};
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::fs::OpenOptionsExt;
+
+    // A client that registers and opens its outgoing FIFO, but never its
+    // incoming one, is skipped instead of stalling the server
+    let mut server = PipeServer::with_name("test_server_stalled_client")?;
+    let mut incoming = server.path().as_os_str().to_owned();
+    incoming.push(".1_999.in");
+    let mut outgoing = server.path().as_os_str().to_owned();
+    outgoing.push(".1_999.out");
+    let mode = nix::sys::stat::Mode::S_IRUSR | nix::sys::stat::Mode::S_IWUSR;
+    nix::unistd::mkfifo(incoming.as_os_str(), mode)?;
+    nix::unistd::mkfifo(outgoing.as_os_str(), mode)?;
+    let _stalled = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(nix::libc::O_NONBLOCK)
+        .open(&outgoing)?;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(server.path())?
+        .write_all(b"1_999\n")?;
+
+    let path = server.path().to_path_buf();
+    let client = thread::spawn(move || -> crate::Result<String> {
+        let mut client = BufReader::new(PipeConnection::connect(&path)?);
+        let mut line = String::new();
+        client.read_line(&mut line)?;
+        Ok(line)
+    });
+    let mut connection = server.accept()?;
+    writeln!(connection, "accepted")?;
+    assert_eq!("accepted\n", client.join().unwrap()?);
+    assert!(!std::path::Path::new(&incoming).exists());
+    assert!(!std::path::Path::new(&outgoing).exists());
+    Ok(())
+}
+
+#[cfg(feature = "uds")]
+#[test]
+fn test_socket_credentials() -> crate::Result<()> {
+    use crate::{OnCleanup, PipeBuilder, PipeKind};
+    use std::io::ErrorKind;
+
+    let uid = nix::unistd::geteuid().as_raw();
+    let builder = PipeBuilder::new()
+        .kind(PipeKind::Socket)
+        .on_cleanup(OnCleanup::Delete);
+    let server = builder
+        .clone()
+        .allow_uids([uid + 1])
+        .with_name("test_socket_creds")?;
+    let mut client = builder.with_name("test_socket_creds")?;
+    assert_eq!(uid, client.peer_credentials()?.uid);
+
+    let mut reader = server.clone();
+    let thread = thread::spawn(move || reader.read(&mut [0]).map(|_| ()));
+    client.write_all(b"x")?;
+    let err = thread.join().unwrap().unwrap_err();
+    assert_eq!(ErrorKind::PermissionDenied, err.kind());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_selector() -> crate::Result<()> {
+    use crate::{Interest, Selector};
+    use std::time::Duration;
+
+    let mut quiet = Pipe::create()?;
+    let mut noisy = Pipe::create()?;
+    let mut selector = Selector::new()?;
+    selector.register(&mut quiet, 1, Interest::READABLE)?;
+    selector.register(&mut noisy, 2, Interest::READABLE)?;
+    assert!(selector.select(Some(Duration::from_millis(10)))?.is_empty());
+
+    noisy.clone().write_all(b"x")?;
+    let events = selector.select(Some(Duration::from_secs(1)))?;
+    assert_eq!(1, events.len());
+    assert_eq!(2, events[0].token());
+    assert!(events[0].is_readable());
+
+    // Writing uses a second descriptor, registered under the same token
+    selector.register(&mut quiet, 1, Interest::READABLE | Interest::WRITABLE)?;
+    selector.deregister(2)?;
+    let events = selector.select(Some(Duration::from_secs(1)))?;
+    assert_eq!(1, events.len());
+    assert!(events[0].is_writable() && !events[0].is_readable());
+
+    // A clone shares the descriptors of the pipe it was cloned from
+    assert!(selector
+        .register(&mut quiet.clone(), 3, Interest::READABLE)
+        .is_err());
+    let events = selector.select(Some(Duration::from_secs(1)))?;
+    assert_eq!(1, events[0].token());
+    Ok(())
+}
+
+#[cfg(feature = "mio")]
+#[test]
+fn test_mio() -> crate::Result<()> {
+    use crate::PipeBuilder;
+    use mio::{Events, Interest, Poll, Token};
+    use std::io::ErrorKind;
+
+    let mut blocking = Pipe::create()?;
+    let mut pipe = PipeBuilder::new().nonblocking(true).create()?;
+    let mut poll = Poll::new()?;
+    let registry = poll.registry();
+    assert!(registry
+        .register(&mut blocking, Token(0), Interest::READABLE)
+        .is_err());
+    registry.register(&mut pipe, Token(1), Interest::READABLE)?;
+
+    let mut buf = [0; 8];
+    assert_eq!(
+        ErrorKind::WouldBlock,
+        pipe.read(&mut buf).unwrap_err().kind()
+    );
+    pipe.clone().write_all(b"ready")?;
+
+    let mut events = Events::with_capacity(4);
+    poll.poll(&mut events, Some(std::time::Duration::from_secs(1)))?;
+    let event = events.iter().next().unwrap();
+    assert_eq!(Token(1), event.token());
+    assert!(event.is_readable());
+    assert_eq!(5, pipe.read(&mut buf)?);
+    assert_eq!(b"ready", &buf[..5]);
+
+    // Only the descriptors that were registered are deregistered
+    let registry = poll.registry();
+    registry.reregister(&mut pipe, Token(1), Interest::WRITABLE)?;
+    registry.deregister(&mut pipe)?;
+    let mut reader = PipeBuilder::new().nonblocking(true).create()?;
+    registry.register(&mut reader, Token(2), Interest::READABLE)?;
+    registry.deregister(&mut reader)?;
+    Ok(())
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn test_async_io() -> crate::Result<()> {
+    use crate::AsyncPipe;
+    use futures_lite::{AsyncBufReadExt, AsyncWriteExt, StreamExt};
+
+    async_io::block_on(async {
+        let mut writer = AsyncPipe::create()?;
+        let reader = AsyncPipe::open(writer.path(), crate::OnCleanup::Delete)?;
+        let task = async {
+            let mut lines = futures_lite::io::BufReader::new(reader).lines();
+            let mut received = vec![];
+            while received.len() < 3 {
+                received.push(lines.next().await.unwrap()?);
+            }
+            Ok::<_, std::io::Error>(received)
+        };
+        let send = async {
+            for i in 1..=3 {
+                writer.write_all(format!("{}\n", i).as_bytes()).await?;
+            }
+            Ok::<_, std::io::Error>(())
+        };
+        let (received, sent) = futures_lite::future::zip(task, send).await;
+        sent?;
+        assert_eq!(vec!["1", "2", "3"], received?);
+
+        // A write-only pipe notices the reader going away without a signal
+        let reader = AsyncPipe::create()?;
+        let mut writer = crate::PipeBuilder::new()
+            .write_only(true)
+            .sigpipe_safe(true)
+            .open_async(reader.path())?;
+        drop(reader);
+        let err = writer.write_all(b"x").await.unwrap_err();
+        assert_eq!(std::io::ErrorKind::BrokenPipe, err.kind());
+        Ok(())
+    })
+}
+
+#[cfg(feature = "static_pipe")]
+#[test]
+fn test_registry() -> crate::Result<()> {
+    use crate::{OnCleanup, PipeBuilder, PipeRegistry};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+    enum Key {
+        Plain,
+        Deleted,
+    }
+    impl AsRef<str> for Key {
+        fn as_ref(&self) -> &str {
+            match self {
+                Key::Plain => "test_registry_plain",
+                Key::Deleted => "test_registry_deleted",
+            }
+        }
+    }
+
+    let registry = PipeRegistry::new();
+    let mut plain = registry.init(Key::Plain)?;
+    let mut deleted = registry.init_with(
+        Key::Deleted,
+        PipeBuilder::new().on_cleanup(OnCleanup::Delete),
+    )?;
+    registry.print(&Key::Plain, "plainX")?;
+    registry.print(&Key::Deleted, "deletedX")?;
+    assert_eq!("plain", read_until_x(&mut plain)?);
+    assert_eq!("deleted", read_until_x(&mut deleted)?);
+    assert!(registry.get(&Key::Plain).is_some());
+
+    // Initializing again closes the pipe it replaces
+    let closed = Arc::new(AtomicBool::new(false));
+    {
+        let closed = closed.clone();
+        registry.on_close(&Key::Deleted, move |_| closed.store(true, Ordering::SeqCst))?;
+    }
+    let mut deleted = registry.init_with(
+        Key::Deleted,
+        PipeBuilder::new().on_cleanup(OnCleanup::Delete),
+    )?;
+    assert!(closed.load(Ordering::SeqCst));
+    // The old pipe does not delete the path the new one took over
+    assert!(deleted.path().exists());
+    registry.print(&Key::Deleted, "againX")?;
+    assert_eq!("again", read_until_x(&mut deleted)?);
+
+    registry.close_all();
+    assert!(!deleted.path().exists());
+    assert!(registry.get(&Key::Plain).is_none());
+    assert!(registry.print(&Key::Plain, "X").is_err());
+    Ok(())
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn test_logger() -> crate::Result<()> {
+    use crate::{static_pipe, PipeLogger};
+    use log::{Level, LevelFilter, Log, Record};
+    use std::time::Duration;
+
+    let mut reader = static_pipe::init("test_logger")?;
+    let logger = PipeLogger::new("test_logger")
+        .level(LevelFilter::Info)
+        .format(|record| format!("{}:{}X", record.level(), record.args()))
+        .timeout(Duration::from_millis(1));
+    let log = |level, message: &str| {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .args(format_args!("{}", message))
+                .build(),
+        )
+    };
+
+    log(Level::Debug, "hidden");
+    log(Level::Warn, "shown");
+    assert_eq!("WARN:shown", read_until_x(&mut reader)?);
+
+    // Nobody reads, so the records that do not fit in the pipe are dropped
+    let record = "x".repeat(1024);
+    for _ in 0..128 {
+        log(Level::Info, &record);
+    }
+    assert!(logger.dropped() > 0);
+    static_pipe::close("test_logger");
+    Ok(())
+}
+
+#[cfg(all(feature = "log", target_os = "linux"))]
+#[test]
+fn test_logger_whole_records() -> crate::Result<()> {
+    use crate::{static_pipe, PipeLogger};
+    use log::{Log, Record};
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    let mut reader = static_pipe::init("test_logger_whole_records")?;
+    let mut writer = static_pipe::get("test_logger_whole_records").unwrap();
+    let page = [b'.'; 4096];
+    let mut filled = 0;
+    while let Ok(written) = writer.write_timeout(&page, Duration::ZERO) {
+        filled += written;
+    }
+    // Leave room for part of the record only
+    let mut buffer = vec![0; 2 * page.len()];
+    reader.read_exact(&mut buffer)?;
+    filled -= buffer.len();
+
+    let record = "x".repeat(3 * page.len()) + "\n";
+    let logger = {
+        let record = record.clone();
+        PipeLogger::new("test_logger_whole_records")
+            .format(move |_| record.clone())
+            .timeout(Duration::from_millis(10))
+    };
+    let log = || logger.log(&Record::builder().args(format_args!("")).build());
+
+    // The record is dropped whole rather than written in part
+    let start = Instant::now();
+    log();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(1, logger.dropped());
+    assert_eq!(filled, reader.bytes_available()?);
+
+    let mut buffer = vec![0; filled];
+    reader.read_exact(&mut buffer)?;
+    log();
+    assert_eq!(1, logger.dropped());
+    let mut buffer = vec![0; record.len()];
+    reader.read_exact(&mut buffer)?;
+    assert_eq!(record.as_bytes(), &buffer[..]);
+    static_pipe::close("test_logger_whole_records");
+    Ok(())
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_trace_layer() -> crate::Result<()> {
+    use crate::{static_pipe, PipeLayer};
+    use std::io::{BufRead, BufReader};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let reader = static_pipe::init("test_trace_layer")?;
+    let subscriber = tracing_subscriber::registry().with(PipeLayer::new("test_trace_layer"));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("work", job = 3);
+        let _entered = span.enter();
+        tracing::warn!(answer = 42, "said \"hi\"");
+    });
+
+    let lines: Vec<String> = BufReader::new(reader)
+        .lines()
+        .take(3)
+        .collect::<std::io::Result<_>>()?;
+    assert!(lines[0].starts_with("{\"type\":\"enter\""));
+    assert!(lines[0].ends_with("\"span\":\"work\",\"id\":1,\"fields\":{\"job\":3}}"));
+    assert!(lines[1].starts_with("{\"type\":\"event\""));
+    assert!(lines[1].contains("\"level\":\"WARN\""));
+    assert!(lines[1].ends_with(
+        "\"span\":\"work\",\"fields\":{\"message\":\"said \\\"hi\\\"\",\"answer\":42}}"
+    ));
+    assert!(lines[2].starts_with("{\"type\":\"exit\""));
+    static_pipe::close("test_trace_layer");
+    Ok(())
+}
+
+#[cfg(feature = "static_pipe")]
+#[test]
+fn test_overflow() -> crate::Result<()> {
+    use crate::{static_pipe, Overflow};
+    use std::io::Read;
+
+    let message = |i: usize| format!("{:<1023}\n", i);
+    let mut reader = static_pipe::init("test_overflow")?;
+
+    // Fill the pipe without blocking and count the rest as dropped
+    static_pipe::set_overflow("test_overflow", Overflow::DropNewest)?;
+    for i in 0..200 {
+        static_pipe::print("test_overflow", &message(i))?;
+    }
+    let stats = static_pipe::stats("test_overflow").unwrap();
+    assert!(stats.written > 0 && stats.dropped > 0);
+    assert_eq!(200 * 1024, stats.written + stats.dropped);
+
+    static_pipe::set_overflow("test_overflow", Overflow::Error)?;
+    let err = static_pipe::print("test_overflow", &message(200)).unwrap_err();
+    assert_eq!(
+        std::io::ErrorKind::WouldBlock,
+        std::io::Error::from(err).kind()
+    );
+
+    // Only the two newest messages fit in the ring buffer
+    static_pipe::set_overflow("test_overflow", Overflow::DropOldest(2048))?;
+    for i in 201..210 {
+        assert_eq!(1024, static_pipe::print("test_overflow", &message(i))?);
+    }
+    assert_eq!(2048, static_pipe::stats("test_overflow").unwrap().buffered);
+
+    let mut buffer = vec![0; stats.written as usize];
+    reader.read_exact(&mut buffer)?;
+    static_pipe::flush("test_overflow")?;
+    let mut buffer = vec![0; 2048];
+    reader.read_exact(&mut buffer)?;
+    assert_eq!(
+        message(208) + &message(209),
+        String::from_utf8(buffer).unwrap()
+    );
+
+    let stats = static_pipe::stats("test_overflow").unwrap();
+    assert_eq!(0, stats.buffered);
+    assert_eq!(210 * 1024, stats.written + stats.dropped);
+    static_pipe::close("test_overflow");
+    Ok(())
+}
+
+#[cfg(feature = "static_pipe")]
+#[test]
+fn test_overflow_partial_message() -> crate::Result<()> {
+    use crate::{static_pipe, Overflow};
+    use std::io::Read;
+
+    // Messages longer than PIPE_BUF are written in parts, leaving one partly
+    // written once the pipe is full
+    let message = |i: usize| format!("{:<9999}\n", i);
+    let mut reader = static_pipe::init("test_overflow_partial_message")?;
+    static_pipe::set_overflow(
+        "test_overflow_partial_message",
+        Overflow::DropOldest(1 << 20),
+    )?;
+    let mut count = 0;
+    while static_pipe::stats("test_overflow_partial_message")
+        .unwrap()
+        .buffered
+        == 0
+    {
+        static_pipe::print("test_overflow_partial_message", &message(count))?;
+        count += 1;
+    }
+
+    // The line waits for the partial message instead of tearing it
+    static_pipe::set_overflow("test_overflow_partial_message", Overflow::DropNewest)?;
+    assert_eq!(
+        0,
+        static_pipe::print("test_overflow_partial_message", "tail\n")?
+    );
+    let stats = static_pipe::stats("test_overflow_partial_message").unwrap();
+    let mut buffer = vec![0; stats.written as usize];
+    reader.read_exact(&mut buffer)?;
+    assert_eq!(
+        5,
+        static_pipe::print("test_overflow_partial_message", "tail\n")?
+    );
+
+    let written = static_pipe::stats("test_overflow_partial_message")
+        .unwrap()
+        .written;
+    let mut rest = vec![0; (written - stats.written) as usize];
+    reader.read_exact(&mut rest)?;
+    buffer.extend(rest);
+    let text = String::from_utf8(buffer).unwrap();
+    let (last, lines) = text
+        .lines()
+        .collect::<Vec<_>>()
+        .split_last()
+        .map(|(l, r)| (*l, r.to_vec()))
+        .unwrap();
+    assert_eq!("tail", last);
+    assert!(!lines.is_empty());
+    assert!(lines.iter().all(|line| line.len() == 9999));
+    static_pipe::close("test_overflow_partial_message");
+    Ok(())
+}
+
+#[cfg(feature = "static_pipe")]
+#[test]
+fn test_write_timeout_overflow() -> crate::Result<()> {
+    use crate::{static_pipe, Overflow};
+    use std::io::Read;
+
+    let message = |i: usize| format!("{:<1023}\n", i);
+    let mut reader = static_pipe::init("test_write_timeout_overflow")?;
+    static_pipe::set_overflow("test_write_timeout_overflow", Overflow::DropOldest(1 << 20))?;
+    let mut count = 0;
+    while static_pipe::stats("test_write_timeout_overflow")
+        .unwrap()
+        .buffered
+        == 0
+    {
+        static_pipe::print("test_write_timeout_overflow", &message(count))?;
+        count += 1;
+    }
+
+    // The line waits behind the ring buffer instead of jumping ahead of it
+    let registry = static_pipe::registry();
+    let timeout = std::time::Duration::from_millis(10);
+    assert_eq!(
+        5,
+        registry.write_timeout("test_write_timeout_overflow", b"last\n", timeout)?
+    );
+    let len = count * 1024 + 5;
+    let thread = std::thread::spawn(move || {
+        let mut buffer = vec![0; len];
+        reader.read_exact(&mut buffer).map(|_| buffer)
+    });
+    static_pipe::flush("test_write_timeout_overflow")?;
+    let buffer = String::from_utf8(thread.join().unwrap()?).unwrap();
+    assert!(buffer.ends_with(&(message(count - 1) + "last\n")));
+    static_pipe::close("test_write_timeout_overflow");
+    Ok(())
+}
+
+#[cfg(feature = "static_pipe")]
+#[test]
+fn test_async_static_pipe() -> crate::Result<()> {
+    use crate::static_pipe;
+    use std::io::{BufRead, BufReader};
+
+    let reader = static_pipe::init_async("test_async_static_pipe", 4)?;
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            std::thread::spawn(move || {
+                for j in 0..10 {
+                    pprintln!("test_async_static_pipe", "{} {}", i, j).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    static_pipe::flush("test_async_static_pipe")?;
+
+    let stats = static_pipe::stats("test_async_static_pipe").unwrap();
+    assert_eq!(40 * 4, stats.written);
+    assert_eq!(0, stats.dropped);
+    assert_eq!(0, stats.buffered);
+
+    let mut lines: Vec<String> = BufReader::new(reader)
+        .lines()
+        .take(40)
+        .collect::<std::io::Result<_>>()?;
+    lines.sort();
+    assert_eq!("0 0", lines[0]);
+    assert_eq!("3 9", lines[39]);
+    static_pipe::close("test_async_static_pipe");
+    assert!(static_pipe::print("test_async_static_pipe", "X").is_err());
+    Ok(())
+}
+
+#[cfg(feature = "static_pipe")]
+#[test]
+fn test_async_static_pipe_unread() -> crate::Result<()> {
+    use crate::{static_pipe, Overflow};
+
+    // Nobody reads, so flushing and closing drop what does not fit
+    let _reader = static_pipe::init_async("test_async_static_pipe_unread", 4)?;
+    static_pipe::set_overflow("test_async_static_pipe_unread", Overflow::DropNewest)?;
+    let message = "x".repeat(1024);
+    for _ in 0..128 {
+        static_pipe::print("test_async_static_pipe_unread", &message)?;
+    }
+    static_pipe::flush("test_async_static_pipe_unread")?;
+    let stats = static_pipe::stats("test_async_static_pipe_unread").unwrap();
+    assert!(stats.dropped > 0);
+    assert_eq!(128 * 1024, stats.written + stats.dropped);
+    static_pipe::print("test_async_static_pipe_unread", &message)?;
+
+    // The ring buffer limits the bytes queued for the thread
+    static_pipe::set_overflow("test_async_static_pipe_unread", Overflow::DropOldest(2048))?;
+    for _ in 0..16 {
+        static_pipe::print("test_async_static_pipe_unread", &message)?;
+    }
+    let stats = static_pipe::stats("test_async_static_pipe_unread").unwrap();
+    assert!(stats.buffered <= 2048);
+
+    // A message longer than the ring buffer is dropped as a whole
+    let long = "x".repeat(4096);
+    assert_eq!(
+        0,
+        static_pipe::print("test_async_static_pipe_unread", &long)?
+    );
+    let dropped = static_pipe::stats("test_async_static_pipe_unread")
+        .unwrap()
+        .dropped;
+    assert_eq!(stats.dropped + 4096, dropped);
+    static_pipe::close("test_async_static_pipe_unread");
+    Ok(())
+}
+
+#[test]
+fn test_pwrite() -> crate::Result<()> {
+    let mut pipe = Pipe::create()?;
+    let mut reader = pipe.clone();
+    assert_eq!(4, pwrite!(pipe, "{}+{}=", 1, 1)?);
+    assert_eq!(2, pwriteln!(&mut pipe, "{}", 2)?);
+    assert_eq!(1, pwrite!(pipe, "X")?);
+    assert_eq!("1+1=2\n", read_until_x(&mut reader)?);
+
+    // Longer text is written in chunks of PIPE_BUF bytes
+    let mut chunks = vec![];
+    let mut writer = std::io::BufWriter::new(&mut chunks);
+    let long = "a".repeat(crate::PIPE_BUF * 2 + 1);
+    assert_eq!(long.len() + 1, pwriteln!(&mut writer, "{}", long)?);
+    drop(writer);
+    assert_eq!(long + "\n", String::from_utf8(chunks).unwrap());
+
+    #[cfg(feature = "static_pipe")]
+    {
+        let mut reader = crate::static_pipe::init("test_pwrite")?;
+        assert_eq!(4, pwriteln!("test_pwrite", "{}X", 42)?);
+        assert_eq!("42", read_until_x(&mut reader)?);
+        crate::static_pipe::close("test_pwrite");
+        assert!(pwrite!("test_pwrite", "X").is_err());
+
+        let registry = crate::PipeRegistry::new();
+        let mut reader = registry.init("test_pwrite_registry")?;
+        assert_eq!(
+            4,
+            pwriteln!((&registry, "test_pwrite_registry"), "{}X", 42)?
+        );
+        assert_eq!("42", read_until_x(&mut reader)?);
+        registry.close("test_pwrite_registry");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "static_pipe")]
+#[test]
+fn test_static_lifecycle() -> crate::Result<()> {
+    use crate::{static_pipe, OnCleanup, Overflow, PipeBuilder};
+
+    let builder = PipeBuilder::new().on_cleanup(OnCleanup::Delete);
+    let mut reader = static_pipe::init_with("test_static_lifecycle", builder)?;
+    assert!(static_pipe::is_initialized("test_static_lifecycle"));
+    assert!(!static_pipe::is_initialized(
+        "test_static_lifecycle_missing"
+    ));
+
+    let (sender, closed) = std::sync::mpsc::channel();
+    static_pipe::on_close("test_static_lifecycle", move |path| {
+        sender.send(path.to_path_buf()).unwrap();
+    })?;
+    static_pipe::print("test_static_lifecycle", "helloX")?;
+    assert_eq!("hello", read_until_x(&mut reader)?);
+
+    let info = static_pipe::list()
+        .into_iter()
+        .find(|info| info.key == "test_static_lifecycle")
+        .unwrap();
+    assert_eq!(reader.path(), info.path);
+    assert_eq!(Overflow::Block, info.overflow);
+    assert!(!info.asynchronous);
+    assert_eq!(6, info.stats.written);
+
+    static_pipe::close("test_static_lifecycle");
+    assert_eq!(info.path, closed.try_recv().unwrap());
+    assert!(!info.path.exists());
+    assert!(!static_pipe::is_initialized("test_static_lifecycle"));
+    assert!(static_pipe::list()
+        .iter()
+        .all(|info| info.key != "test_static_lifecycle"));
+    Ok(())
+}
+
+#[test]
+fn test_line_reader() -> crate::Result<()> {
+    use crate::{Delimiter, Error, LineReader};
+    use std::io::BufReader;
+
+    // A small buffer makes the long line span several reads
+    let input: &[u8] = b"short\r\nthis line is too long\r\nexact\r\n\xffX\r\nlast";
+    let mut lines =
+        LineReader::new(BufReader::with_capacity(4, input), 5).delimiter(Delimiter::CrLf);
+    assert_eq!("short", lines.next().unwrap()?);
+    assert!(matches!(lines.next(), Some(Err(Error::LineTooLong(5)))));
+    assert_eq!("exact", lines.next().unwrap()?);
+    assert!(matches!(lines.next(), Some(Err(Error::InvalidUtf8))));
+    assert_eq!(b"last", lines.read_bytes().unwrap()?.as_slice());
+    assert!(lines.next().is_none());
+
+    let input: &[u8] = b"a\0bb\0\r\0";
+    let lines: Vec<_> = LineReader::new(input, 1)
+        .delimiter(Delimiter::Nul)
+        .map(|line| line.ok())
+        .collect();
+    assert_eq!(
+        vec![Some("a".to_string()), None, Some("\r".to_string())],
+        lines
+    );
+
+    let mut pipe = Pipe::create()?;
+    let reader = pipe.clone();
+    pipe.write_all(b"1\n22\n333\n4444\n")?;
+    let lines: Vec<_> = reader
+        .lines_bounded(3)
+        .take(4)
+        .map(|line| line.ok())
+        .collect();
+    assert_eq!(
+        vec![
+            Some("1".to_string()),
+            Some("22".to_string()),
+            Some("333".to_string()),
+            None
+        ],
+        lines
+    );
+    Ok(())
+}
+
+#[test]
+fn test_records() -> crate::Result<()> {
+    use crate::{RecordReader, RecordWriter, RECORD_ESCAPE};
+
+    const CANCEL: u8 = 24;
+    let records: [&[u8]; 4] = [b"plain", &[CANCEL, RECORD_ESCAPE, CANCEL], b"", b"-<>-"];
+
+    let mut writer = RecordWriter::new(vec![], b"<>")?;
+    for record in records {
+        writer.write_record(record)?;
+    }
+    let written = writer.into_inner();
+    let read: Vec<_> =
+        RecordReader::new(written.as_slice(), b"<>")?.collect::<crate::Result<_>>()?;
+    assert_eq!(records.to_vec(), read);
+
+    // Truncated and malformed streams
+    let mut reader = RecordReader::new(&written[..written.len() - 1], b"<>")?;
+    assert_eq!(3, reader.by_ref().take(3).count());
+    assert!(reader.next().unwrap().is_err());
+    assert!(RecordReader::new(&b"a<b"[..], b"<>")?
+        .next()
+        .unwrap()
+        .is_err());
+    assert!(RecordReader::with_escape(&b""[..], b"<>", b'<').is_err());
+
+    let pipe = Pipe::create()?;
+    let mut writer = RecordWriter::new(pipe.clone(), &[CANCEL])?;
+    writer.write_record(&[1, CANCEL, 2])?;
+    writer.write_record(&[3])?;
+    let read: Vec<_> = pipe
+        .records(&[CANCEL])?
+        .take(2)
+        .collect::<crate::Result<_>>()?;
+    assert_eq!(vec![vec![1, CANCEL, 2], vec![3]], read);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_rpc() -> crate::Result<()> {
+    use crate::rpc::{Client, Server};
+    use crate::Error;
+    use std::time::Duration;
+
+    let mut server = Server::with_name("test_rpc")?;
+    thread::spawn(move || {
+        server.serve(|method, payload| match method {
+            "echo" => Ok(payload.to_vec()),
+            "slow" => {
+                thread::sleep(Duration::from_millis(200));
+                Ok(payload.to_vec())
+            }
+            _ => Err(Error::Ipipe("Unknown method")),
+        })
+    });
+
+    let client = Arc::new(Client::with_name("test_rpc")?);
+    let slow = {
+        let client = client.clone();
+        thread::spawn(move || client.call("slow", b"slow"))
+    };
+    // The fast call is answered while the slow one is still running
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(b"fast".to_vec(), client.call("echo", b"fast")?);
+    assert!(!slow.is_finished());
+    assert_eq!(b"slow".to_vec(), slow.join().unwrap()?);
+
+    let err = client.call("missing", b"").unwrap_err();
+    assert!(matches!(err, Error::Remote(message) if message == "Unknown method"));
+    let err = client
+        .call_timeout("slow", b"", Duration::from_millis(10))
+        .unwrap_err();
+    assert_eq!(
+        std::io::ErrorKind::TimedOut,
+        std::io::Error::from(err).kind()
+    );
+    assert_eq!(b"after".to_vec(), client.call("echo", b"after")?);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_rpc_max_calls() -> crate::Result<()> {
+    use crate::rpc::{Client, Server};
+    use std::time::{Duration, Instant};
+
+    let mut server = Server::with_name("test_rpc_max_calls")?.max_calls(1);
+    thread::spawn(move || {
+        server.serve(|_, payload| {
+            thread::sleep(Duration::from_millis(100));
+            Ok(payload.to_vec())
+        })
+    });
+
+    // Calls beyond the limit wait for a free thread
+    let client = Arc::new(Client::with_name("test_rpc_max_calls")?);
+    let start = Instant::now();
+    let calls: Vec<_> = (0..2)
+        .map(|_| {
+            let client = client.clone();
+            thread::spawn(move || client.call("slow", b"x"))
+        })
+        .collect();
+    for call in calls {
+        assert_eq!(b"x".to_vec(), call.join().unwrap()?);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    Ok(())
+}
+
+#[test]
+fn test_framed() -> crate::Result<()> {
+    use crate::Framed;
+
+    let mut framed = Framed::new(std::io::Cursor::new(vec![]));
+    framed.write_frame(b"first")?;
+    framed.write_frame(b"")?;
+    framed.write_frame(&[0; 10])?;
+    framed.get_mut().set_position(0);
+    let mut framed = framed.max_len(5);
+    assert_eq!(Some(b"first".to_vec()), framed.read_frame()?);
+    assert_eq!(Some(vec![]), framed.read_frame()?);
+    assert!(framed.read_frame().is_err());
+
+    let mut framed = Framed::new(&[0, 0, 0, 1][..]);
+    assert!(framed.read_frame().is_err());
+    let mut framed = Framed::new(&[][..]);
+    assert_eq!(None, framed.read_frame()?);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_broadcast() -> crate::Result<()> {
+    use crate::{Broadcaster, OnCleanup, SlowSubscriber};
+    use std::time::Duration;
+
+    let mut broadcaster =
+        Broadcaster::with_name("test_broadcast")?.refresh_interval(Duration::from_millis(10));
+    let path = broadcaster.path().to_path_buf();
+    let mut first = Broadcaster::subscribe(&path, "first")?;
+    let mut second = Broadcaster::subscribe(&path, "second")?;
+    thread::spawn(move || broadcaster.run());
+
+    let mut input = Pipe::open(&path, OnCleanup::NoDelete)?;
+    write!(input, "helloX")?;
+    assert_eq!("hello", read_until_x(&mut first)?);
+    assert_eq!("hello", read_until_x(&mut second)?);
+
+    // Nobody reads, so the subscriber FIFOs fill up
+    let mut broadcaster = Broadcaster::with_name("test_broadcast_slow")?;
+    let _first = Broadcaster::subscribe(broadcaster.path(), "first")?;
+    let second = Broadcaster::subscribe(broadcaster.path(), "second")?;
+    broadcaster.refresh()?;
+    assert_eq!(2, broadcaster.subscriber_count());
+    let message = [b'.'; 1024];
+    for _ in 0..100 {
+        broadcaster.broadcast(&message)?;
+    }
+    assert!(broadcaster.dropped() > 0);
+    assert_eq!(2, broadcaster.subscriber_count());
+
+    let mut broadcaster = broadcaster.slow_subscribers(SlowSubscriber::Disconnect);
+    assert_eq!(0, broadcaster.broadcast(&message)?);
+    assert_eq!(0, broadcaster.subscriber_count());
+    assert!(!second.path().exists());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_reconnecting_writer() -> crate::Result<()> {
+    use crate::{Backoff, OnCleanup, PipeBuilder, ReconnectingWriter};
+    use std::time::Duration;
+
+    let path = crate::path_from_name("test_reconnecting_writer");
+    let mut writer = ReconnectingWriter::open(&path)?
+        .backoff(Backoff::Fixed(Duration::ZERO))
+        .max_messages(2);
+    assert!(!writer.is_connected());
+    assert!(PipeBuilder::new()
+        .write_only(true)
+        .nonblocking(true)
+        .open(&path)
+        .is_err());
+
+    // Only the last two messages are kept until a reader arrives
+    write!(writer, "one")?;
+    write!(writer, "two")?;
+    write!(writer, "threeX")?;
+    assert_eq!(1, writer.dropped());
+    assert_eq!(9, writer.buffered());
+
+    let mut reader = Pipe::open(&path, OnCleanup::NoDelete)?;
+    write!(writer, "fourX")?;
+    assert!(writer.is_connected());
+    assert_eq!(0, writer.buffered());
+    assert_eq!("twothree", read_until_x(&mut reader)?);
+    assert_eq!("four", read_until_x(&mut reader)?);
+
+    // The reader restarts
+    reader.close()?;
+    write!(writer, "fiveX")?;
+    assert!(!writer.is_connected());
+    assert_eq!(5, writer.buffered());
+    let mut reader = Pipe::open(&path, OnCleanup::Delete)?;
+    assert!(writer.reconnect()?);
+    assert_eq!("five", read_until_x(&mut reader)?);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sigpipe_safe() -> crate::Result<()> {
+    use crate::{OnCleanup, PipeBuilder};
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+    use std::io::ErrorKind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static SIGPIPES: AtomicUsize = AtomicUsize::new(0);
+    extern "C" fn count_sigpipe(_: nix::libc::c_int) {
+        SIGPIPES.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let path = crate::path_from_name("test_sigpipe_safe");
+    let reader = Pipe::open(&path, OnCleanup::NoDelete)?;
+    let mut writer = PipeBuilder::new().write_only(true).open(&path)?;
+    assert!(writer.is_write_only());
+    assert!(writer.read(&mut [0]).is_err());
+    reader.close()?;
+
+    let action = SigAction::new(
+        SigHandler::Handler(count_sigpipe),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    let previous = unsafe { sigaction(Signal::SIGPIPE, &action) }?;
+    let unprotected = writer.write(b"hello");
+    let raised = SIGPIPES.load(Ordering::SeqCst);
+    writer.set_sigpipe_safe(true);
+    let protected = writer.write(b"hello");
+    let raised_after = SIGPIPES.load(Ordering::SeqCst);
+    unsafe { sigaction(Signal::SIGPIPE, &previous) }?;
+
+    assert_eq!(ErrorKind::BrokenPipe, unprotected.unwrap_err().kind());
+    assert_eq!(1, raised);
+    assert_eq!(ErrorKind::BrokenPipe, protected.unwrap_err().kind());
+    assert_eq!(raised, raised_after);
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_vectored() -> crate::Result<()> {
+    use std::io::{IoSlice, IoSliceMut};
+
+    let mut pipe = Pipe::create()?;
+    assert!(pipe.is_write_vectored());
+    let written = pipe.write_vectored(&[
+        IoSlice::new(b"hello "),
+        IoSlice::new(b""),
+        IoSlice::new(b"world"),
+    ])?;
+    assert_eq!(11, written);
+
+    let (mut first, mut second) = ([0; 6], [0; 5]);
+    let read =
+        pipe.read_vectored(&mut [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)])?;
+    assert_eq!(11, read);
+    assert_eq!(b"hello ", &first);
+    assert_eq!(b"world", &second);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_drain() -> crate::Result<()> {
+    use std::io::ErrorKind;
+    use std::time::Duration;
+
+    let mut pipe = Pipe::create()?;
+    pipe.write_all(b"helloX")?;
+    pipe.flush()?;
+    let error = pipe.drain(Duration::from_millis(10)).unwrap_err();
+    assert_eq!(ErrorKind::TimedOut, error.kind());
+
+    // Nobody reads, so the wait ends with the timeout
+    let start = std::time::Instant::now();
+    let error = pipe.drain(Duration::ZERO).unwrap_err();
+    assert_eq!(ErrorKind::TimedOut, error.kind());
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    let mut reader = pipe.clone();
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        read_until_x(&mut reader)
+    });
+    pipe.drain(Duration::from_secs(5))?;
+    assert_eq!("hello", handle.join().unwrap()?);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pipe_stats() -> crate::Result<()> {
+    let mut pipe = Pipe::create()?;
+    assert_eq!(0, pipe.bytes_available()?);
+    pipe.write_all(b"hello")?;
+    assert_eq!(5, pipe.bytes_available()?);
+
+    let stats = pipe.stats()?;
+    assert_eq!(5, stats.bytes_available);
+    #[cfg(target_os = "linux")]
+    assert_eq!(Some(pipe.capacity()?), stats.capacity);
+    assert!(stats
+        .capacity
+        .is_none_or(|capacity| capacity >= crate::PIPE_BUF));
+
+    pipe.read_exact(&mut [0; 5])?;
+    assert_eq!(0, pipe.bytes_available()?);
+    Ok(())
+}
+
+#[test]
+fn test_peek() -> crate::Result<()> {
+    use crate::{Framed, LineReader};
+    use std::io::BufRead;
+
+    let mut pipe = Pipe::create()?;
+    let mut peekable = pipe.clone().peekable();
+    pipe.write_all(b"TEXT first\nsecond\n")?;
+    let mut magic = [0; 4];
+    assert_eq!(4, peekable.peek(&mut magic)?);
+    assert_eq!(b"TEXT", &magic);
+    assert_eq!(4, peekable.peek(&mut magic)?);
+    assert_eq!(b"TEXT", peekable.buffer());
+
+    let mut lines = LineReader::new(peekable, 64);
+    assert_eq!("TEXT first", lines.next().unwrap()?);
+    assert_eq!("second", lines.next().unwrap()?);
+
+    // Peek at the header of a frame, then read the frame
+    let mut peekable = lines.into_inner();
+    Framed::new(&mut pipe).write_frame(b"hello")?;
+    let mut header = [0; 4];
+    peekable.peek(&mut header)?;
+    assert_eq!(5, u32::from_be_bytes(header));
+    assert_eq!(
+        Some(b"hello".to_vec()),
+        Framed::new(&mut peekable).read_frame()?
+    );
+
+    pipe.write_all(b"abc")?;
+    assert_eq!(b"abc", peekable.fill_buf()?);
+    peekable.consume(1);
+    let mut rest = [0; 2];
+    peekable.read_exact(&mut rest)?;
+    assert_eq!(b"bc", &rest);
+    Ok(())
+}
+
+#[test]
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+fn test_compression() -> crate::Result<()> {
+    use crate::{CompressedReader, CompressedWriter, Compression, LineReader};
+
+    let compressions = [
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(3),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4,
+    ];
+    for compression in compressions {
+        let pipe = Pipe::create()?;
+        let mut writer = CompressedWriter::new(pipe.clone())
+            .compression(compression)
+            .frame_len(1024);
+        let line = "{\"cpu\": 0.42, \"memory\": 0.24, \"disk\": 0.5}\n".repeat(10);
+        writer.write_all(line.as_bytes())?;
+        assert_eq!(0, writer.stats().frames);
+        writer.flush()?;
+        let stats = writer.stats();
+        assert_eq!(1, stats.frames);
+        assert_eq!(line.len() as u64, stats.uncompressed);
+        assert!(stats.ratio() > 1.0);
+
+        // Readers may start at a frame boundary
+        let mut reader = CompressedReader::new(pipe.clone());
+        writer.write_frame(b"skipped\n")?;
+        writer.write_frame(b"first\nsecond\n")?;
+        assert_eq!(line.as_bytes(), reader.read_frame()?.unwrap());
+        assert_eq!(b"skipped\n".to_vec(), reader.read_frame()?.unwrap());
+        let mut lines = LineReader::new(reader, 64);
+        assert_eq!("first", lines.next().unwrap()?);
+        assert_eq!("second", lines.next().unwrap()?);
+        drop(writer);
+    }
+    Ok(())
+}
@@ -0,0 +1,218 @@
+//! This module contains a `tracing_subscriber::Layer` that streams events
+//! and span enter/exit records into a static pipe as newline-delimited JSON.
+
+use crate::static_pipe;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// A layer that writes one JSON object per line into a static pipe for every
+/// event and every time a span is entered or exited, so that an external
+/// viewer can attach to a running process. Like `PipeLogger`, it never blocks
+/// the caller for longer than the timeout: lines that cannot be written whole
+/// in time are dropped whole and counted.
+///
+/// Each line has a `type` of `event`, `enter` or `exit`, a `time` in
+/// milliseconds since the unix epoch, and the `level`, `target`, `span` name
+/// and `fields` it refers to. Events also carry the name of the span they
+/// happened in, if any.
+///
+/// ```no_run
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let _reader = ipipe::init("my_traces").unwrap();
+/// let subscriber = tracing_subscriber::registry().with(ipipe::PipeLayer::new("my_traces"));
+/// tracing::subscriber::set_global_default(subscriber).unwrap();
+/// ```
+pub struct PipeLayer {
+    name: String,
+    timeout: Duration,
+    dropped: AtomicU64,
+}
+
+/// The JSON fields recorded for a span, stored in its extensions
+struct SpanFields(String);
+
+impl PipeLayer {
+    /// Create a layer writing to the static pipe with the given name, which
+    /// must be initialized before anything is traced. Defaults to a 10ms
+    /// timeout.
+    pub fn new(name: &str) -> Self {
+        PipeLayer {
+            name: name.to_string(),
+            timeout: Duration::from_millis(10),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// The longest the caller waits for a line to be written before it is
+    /// dropped
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The number of lines that were dropped
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, line: &str) {
+        if !static_pipe::registry().write_record(self.name.as_str(), line.as_bytes(), self.timeout)
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn send_span<S>(&self, kind: &str, id: &Id, ctx: Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        if let Some(span) = ctx.span(id) {
+            let metadata = span.metadata();
+            let mut line = header(kind, metadata.level().as_str(), metadata.target());
+            line.push_str(",\"span\":");
+            push_str(&mut line, metadata.name());
+            line.push_str(",\"id\":");
+            line.push_str(&id.into_u64().to_string());
+            line.push_str(",\"fields\":{");
+            if let Some(fields) = span.extensions().get::<SpanFields>() {
+                line.push_str(&fields.0);
+            }
+            line.push_str("}}\n");
+            self.send(&line);
+        }
+    }
+}
+
+impl<S> Layer<S> for PipeLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut visitor = JsonVisitor(String::new());
+            attrs.record(&mut visitor);
+            span.extensions_mut().insert(SpanFields(visitor.0));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut extensions = span.extensions_mut();
+            if let Some(fields) = extensions.get_mut::<SpanFields>() {
+                let mut visitor = JsonVisitor(std::mem::take(&mut fields.0));
+                values.record(&mut visitor);
+                fields.0 = visitor.0;
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = header("event", metadata.level().as_str(), metadata.target());
+        if let Some(span) = ctx.event_span(event) {
+            line.push_str(",\"span\":");
+            push_str(&mut line, span.name());
+        }
+        line.push_str(",\"fields\":{");
+        let mut visitor = JsonVisitor(String::new());
+        event.record(&mut visitor);
+        line.push_str(&visitor.0);
+        line.push_str("}}\n");
+        self.send(&line);
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.send_span("enter", id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.send_span("exit", id, ctx);
+    }
+}
+
+/// Collects fields as the comma-separated members of a JSON object
+struct JsonVisitor(String);
+
+impl JsonVisitor {
+    fn key(&mut self, field: &Field) {
+        if !self.0.is_empty() {
+            self.0.push(',');
+        }
+        push_str(&mut self.0, field.name());
+        self.0.push(':');
+    }
+}
+
+impl Visit for JsonVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.key(field);
+        push_str(&mut self.0, &format!("{:?}", value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.key(field);
+        push_str(&mut self.0, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.key(field);
+        let _ = write!(self.0, "{}", value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.key(field);
+        let _ = write!(self.0, "{}", value);
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.key(field);
+        if value.is_finite() {
+            let _ = write!(self.0, "{}", value);
+        } else {
+            push_str(&mut self.0, &value.to_string());
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.key(field);
+        let _ = write!(self.0, "{}", value);
+    }
+}
+
+/// Starts a JSON line with the members every line has
+fn header(kind: &str, level: &str, target: &str) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis());
+    let mut line = format!("{{\"type\":\"{}\",\"time\":{},\"level\":", kind, time);
+    push_str(&mut line, level);
+    line.push_str(",\"target\":");
+    push_str(&mut line, target);
+    line
+}
+
+/// Pushes a quoted and escaped JSON string
+fn push_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,130 @@
+//! This module contains the Unix domain socket backend used by
+//! `PipeKind::Socket`.
+
+use crate::{Error, PeerPolicy, Result};
+use nix::errno::Errno;
+use nix::sys::stat::{stat, SFlag};
+use std::io::ErrorKind;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A `SOCK_STREAM` socket shared between a pipe and its clones. The first
+/// pipe opened at a path listens on it and lazily accepts a single peer the
+/// first time it is read from or written to.
+#[derive(Debug)]
+pub(crate) struct Socket {
+    listener: Mutex<Option<UnixListener>>,
+    stream: Mutex<Option<Arc<UnixStream>>>,
+    is_listener: bool,
+    policy: PeerPolicy,
+    nonblocking: AtomicBool,
+}
+
+impl Socket {
+    /// Connect to the socket at the given path, or listen on it if no other
+    /// pipe is currently listening. Peers are checked against the policy as
+    /// they connect.
+    pub(crate) fn open(path: &Path, policy: PeerPolicy) -> Result<Self> {
+        loop {
+            match stat(path) {
+                Ok(file_stat) => {
+                    // Error out if file is not a socket
+                    if file_stat.st_mode & SFlag::S_IFMT.bits() != SFlag::S_IFSOCK.bits() {
+                        Err(Error::InvalidPath)?;
+                    }
+                    match UnixStream::connect(path) {
+                        Ok(stream) => {
+                            policy.check_socket(&stream)?;
+                            break Ok(Socket {
+                                listener: Mutex::new(None),
+                                stream: Mutex::new(Some(Arc::new(stream))),
+                                is_listener: false,
+                                policy,
+                                nonblocking: AtomicBool::new(false),
+                            });
+                        }
+                        // Nobody is listening, so the socket is stale
+                        Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                            std::fs::remove_file(path)?;
+                        }
+                        Err(e) => Err(e)?,
+                    }
+                }
+                Err(Errno::ENOENT) => match UnixListener::bind(path) {
+                    Ok(listener) => {
+                        break Ok(Socket {
+                            listener: Mutex::new(Some(listener)),
+                            stream: Mutex::new(None),
+                            is_listener: true,
+                            policy,
+                            nonblocking: AtomicBool::new(false),
+                        })
+                    }
+                    // Another pipe raced us to the path, connect to it instead
+                    Err(e) if e.kind() == ErrorKind::AddrInUse => {}
+                    Err(e) => Err(e)?,
+                },
+                Err(e) => Err(e)?,
+            }
+        }
+    }
+
+    /// Returns the connected stream, blocking until a peer connects if this
+    /// socket is listening and has not accepted one yet. A rejected peer is
+    /// disconnected, and the next call waits for another one.
+    pub(crate) fn stream(&self) -> std::io::Result<Arc<UnixStream>> {
+        let mut stream = self.stream.lock().map_err(|e| Error::Misc(e.to_string()))?;
+        if stream.is_none() {
+            let listener = self
+                .listener
+                .lock()
+                .map_err(|e| Error::Misc(e.to_string()))?;
+            match listener.as_ref() {
+                Some(listener) => {
+                    let (accepted, _) = listener.accept()?;
+                    self.policy.check_socket(&accepted)?;
+                    accepted.set_nonblocking(self.nonblocking.load(Ordering::Relaxed))?;
+                    *stream = Some(Arc::new(accepted));
+                }
+                None => Err(std::io::Error::from(Errno::EBADF))?,
+            }
+        }
+        Ok(stream.as_ref().unwrap().clone())
+    }
+
+    /// Moves the socket into or out of non-blocking mode. A non-blocking
+    /// listener fails with `WouldBlock` instead of waiting for a peer.
+    pub(crate) fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        let listener = self
+            .listener
+            .lock()
+            .map_err(|e| Error::Misc(e.to_string()))?;
+        if let Some(listener) = listener.as_ref() {
+            listener.set_nonblocking(nonblocking)?;
+        }
+        let stream = self.stream.lock().map_err(|e| Error::Misc(e.to_string()))?;
+        if let Some(stream) = stream.as_ref() {
+            stream.set_nonblocking(nonblocking)?;
+        }
+        Ok(())
+    }
+
+    /// Whether this socket was bound by the pipe that owns it
+    pub(crate) fn is_listener(&self) -> bool {
+        self.is_listener
+    }
+
+    /// Closes the socket. Any clone mid-read or mid-write keeps the stream
+    /// open until it completes.
+    pub(crate) fn close(&self) {
+        if let Ok(mut stream) = self.stream.lock() {
+            stream.take();
+        }
+        if let Ok(mut listener) = self.listener.lock() {
+            listener.take();
+        }
+    }
+}
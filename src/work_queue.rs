@@ -0,0 +1,123 @@
+//! This module contains a work queue shared by producer and consumer
+//! processes through a single FIFO.
+
+use crate::{path_from_name, Framed, OnCleanup, Pipe, Result, DEFAULT_MAX_FRAME_LEN, PIPE_BUF};
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// A queue of jobs that any number of producers push into a FIFO and any
+/// number of consumers pop from it, each job being handed whole to exactly
+/// one consumer.
+///
+/// Jobs are framed like `Framed` frames. Producers write a job whose frame
+/// fits in `PIPE_BUF` bytes with a single, atomic, write. Larger jobs take
+/// several writes, so producers hold a lock on `<path>.write.lock` while
+/// writing them, shared for small jobs and exclusive for large ones.
+/// Consumers hold an exclusive lock on `<path>.read.lock` while reading a
+/// job, so that its header and body are read by the same consumer. The lock
+/// files are created next to the FIFO and left in place, since other
+/// processes may still use them.
+///
+/// ```no_run
+/// use ipipe::WorkQueue;
+///
+/// let mut queue = WorkQueue::with_name("my_jobs").unwrap();
+/// queue.push(b"resize image.png").unwrap();
+///
+/// // In any number of worker processes
+/// let mut queue = WorkQueue::with_name("my_jobs").unwrap();
+/// let job = queue.pop().unwrap();
+/// ```
+pub struct WorkQueue {
+    pipe: Pipe,
+    read_lock: File,
+    write_lock: File,
+    max_job_len: usize,
+}
+
+impl WorkQueue {
+    /// Open or create a work queue at the given path
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(WorkQueue {
+            pipe: Pipe::open(path, OnCleanup::NoDelete)?,
+            read_lock: open_lock(path, ".read.lock")?,
+            write_lock: open_lock(path, ".write.lock")?,
+            max_job_len: DEFAULT_MAX_FRAME_LEN,
+        })
+    }
+
+    /// Open or create a work queue with the given name. Note that this is
+    /// just a string name, not a path.
+    pub fn with_name(name: &str) -> Result<Self> {
+        WorkQueue::open(&path_from_name(name))
+    }
+
+    /// Set the longest job `pop` accepts. Longer jobs are skipped and
+    /// reported as `std::io::ErrorKind::InvalidData`. Defaults to
+    /// `DEFAULT_MAX_FRAME_LEN`.
+    pub fn max_job_len(mut self, max_job_len: usize) -> Self {
+        self.max_job_len = max_job_len;
+        self
+    }
+
+    /// Return the path to the FIFO
+    pub fn path(&self) -> &Path {
+        self.pipe.path()
+    }
+
+    /// Push a job onto the queue
+    pub fn push(&mut self, job: &[u8]) -> Result<()> {
+        let arg = match job.len() + 4 <= PIPE_BUF {
+            true => FlockArg::LockShared,
+            false => FlockArg::LockExclusive,
+        };
+        let _lock = lock(&self.write_lock, arg)?;
+        Framed::new(&mut self.pipe).write_frame(job)
+    }
+
+    /// Block until a job is available and pop it off the queue
+    pub fn pop(&mut self) -> Result<Vec<u8>> {
+        let _lock = lock(&self.read_lock, FlockArg::LockExclusive)?;
+        let mut header = [0; 4];
+        self.pipe.read_exact(&mut header)?;
+        let len = u32::from_be_bytes(header) as usize;
+        if len > self.max_job_len {
+            // Skip the job so that the next consumer starts at a header
+            std::io::copy(&mut (&mut self.pipe).take(len as u64), &mut std::io::sink())?;
+            Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Job of {} bytes is longer than {}", len, self.max_job_len),
+            ))?;
+        }
+        let mut job = vec![0; len];
+        self.pipe.read_exact(&mut job)?;
+        Ok(job)
+    }
+}
+
+fn open_lock(path: &Path, suffix: &str) -> Result<File> {
+    let mut lock = path.as_os_str().to_owned();
+    lock.push(suffix);
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o660)
+        .open(lock)?)
+}
+
+/// Locks the file until the returned lock is dropped
+fn lock(file: &File, arg: FlockArg) -> Result<Flock<File>> {
+    loop {
+        match Flock::lock(file.try_clone()?, arg) {
+            Ok(lock) => return Ok(lock),
+            Err((_, Errno::EINTR)) => continue,
+            Err((_, errno)) => Err(errno)?,
+        }
+    }
+}
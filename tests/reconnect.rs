@@ -0,0 +1,40 @@
+#![cfg(target_os = "linux")]
+
+use ipipe::{Backoff, OnCleanup, Pipe, ReconnectingWriter};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// The number of descriptors open in this process. This test has a binary of
+/// its own so that no other test opens descriptors meanwhile.
+fn open_fds() -> usize {
+    std::fs::read_dir("/proc/self/fd").unwrap().count()
+}
+
+#[test]
+fn reconnecting_writer_closes_fds() {
+    let path = std::env::temp_dir().join(format!("ipipe_reconnect_fds_{}", std::process::id()));
+    let mut writer = ReconnectingWriter::open(&path)
+        .unwrap()
+        .backoff(Backoff::Fixed(Duration::ZERO));
+
+    // Each restart reads what was kept while the previous reader was gone
+    let mut restart = |expected: &[u8]| {
+        let mut reader = Pipe::open(&path, OnCleanup::NoDelete).unwrap();
+        write!(writer, "hello").unwrap();
+        assert!(writer.is_connected());
+        let mut message = vec![0; expected.len()];
+        reader.read_exact(&mut message).unwrap();
+        assert_eq!(expected, &message);
+        reader.close().unwrap();
+        write!(writer, "gone").unwrap();
+        assert!(!writer.is_connected());
+    };
+    restart(b"hello");
+    let fds = open_fds();
+    for _ in 0..20 {
+        restart(b"gonehello");
+    }
+    assert_eq!(fds, open_fds());
+    drop(writer);
+    std::fs::remove_file(&path).unwrap();
+}
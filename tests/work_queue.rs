@@ -0,0 +1,84 @@
+#![cfg(unix)]
+
+use ipipe::{WorkQueue, PIPE_BUF};
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+const QUEUE_VAR: &str = "IPIPE_WORK_QUEUE";
+const JOBS: u32 = 60;
+const CONSUMERS: usize = 3;
+
+/// Every third job is too long to be written atomically
+fn job(id: u32) -> Vec<u8> {
+    let len = if id.is_multiple_of(3) {
+        PIPE_BUF * 3
+    } else {
+        100
+    };
+    let mut job = id.to_be_bytes().to_vec();
+    job.extend((0..len).map(|i| (id as usize + i) as u8));
+    job
+}
+
+/// Runs as a consumer process when started by `work_queue_processes`,
+/// printing the id of every job it pops until it pops an empty job
+#[test]
+fn work_queue_consumer() {
+    let Some(path) = std::env::var_os(QUEUE_VAR) else {
+        return;
+    };
+    let mut queue = WorkQueue::open(path.as_ref()).unwrap();
+    loop {
+        let popped = queue.pop().unwrap();
+        if popped.is_empty() {
+            break;
+        }
+        let id = u32::from_be_bytes(popped[..4].try_into().unwrap());
+        assert_eq!(job(id), popped, "Torn job {}", id);
+        println!("job {}", id);
+    }
+}
+
+#[test]
+fn work_queue_processes() {
+    let path = std::env::temp_dir().join(format!("ipipe_work_queue_{}", std::process::id()));
+    let mut queue = WorkQueue::open(&path).unwrap();
+
+    let consumers: Vec<_> = (0..CONSUMERS)
+        .map(|_| {
+            Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "work_queue_consumer", "--nocapture"])
+                .env(QUEUE_VAR, &path)
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+
+    for id in 0..JOBS {
+        queue.push(&job(id)).unwrap();
+    }
+    for _ in 0..CONSUMERS {
+        queue.push(&[]).unwrap();
+    }
+
+    let mut ids = vec![];
+    for mut consumer in consumers {
+        let stdout = consumer.stdout.take().unwrap();
+        for line in BufReader::new(stdout).lines() {
+            // The first line follows the test name the harness prints
+            if let Some((_, id)) = line.unwrap().rsplit_once("job ") {
+                ids.push(id.parse::<u32>().unwrap());
+            }
+        }
+        assert!(consumer.wait().unwrap().success());
+    }
+    ids.sort();
+    assert_eq!((0..JOBS).collect::<Vec<_>>(), ids);
+
+    for suffix in ["", ".read.lock", ".write.lock"] {
+        let mut file = path.clone().into_os_string();
+        file.push(suffix);
+        let _ = std::fs::remove_file(file);
+    }
+}